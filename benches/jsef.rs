@@ -0,0 +1,149 @@
+//! Rough timings of the hot paths, run with `cargo bench`.
+//! 
+//! Uses no benchmark framework, each case reports the best of a few runs.
+
+use std::{hint::black_box, time::{Duration, Instant}};
+
+use jsef::*;
+
+
+const RUNS: usize = 10;
+
+
+fn best<T>(mut run: impl FnMut() -> T) -> Duration {
+	let mut best = Duration::MAX;
+	
+	for _ in 0..RUNS {
+		let start = Instant::now();
+		black_box(run());
+		best = best.min(start.elapsed());
+	}
+	
+	best
+}
+
+fn bench<T>(name: &str, run: impl FnMut() -> T) {
+	println!("{name:<40} {:>12.2?}", best(run));
+}
+
+/// Like [`bench`], but also reports how many MB of `len` bytes of input were processed per second.
+fn bench_throughput<T>(name: &str, len: usize, run: impl FnMut() -> T) {
+	let best = best(run);
+	let rate = len as f64 / best.as_secs_f64() / 1e6;
+	println!("{name:<40} {best:>12.2?} {rate:>8.0} MB/s");
+}
+
+/// A root dict with `pairs` pairs of short keys, quoted strings and small lists.
+fn large_dict(pairs: usize) -> String {
+	let mut source = String::from("{\n");
+	
+	for i in 0..pairs {
+		source += &format!("\tkey_{i} = {{name = \"value number {i}\" tags = [a b c] id = {i}}}\n");
+	}
+	
+	source.push('}');
+	source
+}
+
+/// A root dict with `pairs` pairs whose values are bare words starting with `word`.
+fn word_dict(pairs: usize, word: &str) -> String {
+	let mut source = String::from("{\n");
+	
+	for i in 0..pairs {
+		source += &format!("\tkey_{i} = {word}{i}\n");
+	}
+	
+	source.push('}');
+	source
+}
+/// A root list of about `len` bytes of bare words, each `word_len` chars long.
+fn word_list(len: usize, word_len: usize) -> String {
+	let mut source = String::with_capacity(len + word_len);
+	
+	for i in 0.. {
+		if source.len() >= len {break;}
+		
+		let word = format!("word_{i}_");
+		source.extend(word.chars().cycle().take(word_len));
+		source.push('\n');
+	}
+	
+	source
+}
+
+
+/// Keeps track of the line and col of the output like the composer used to for every char.
+struct Counting {
+	out: String,
+	line: usize,
+	col: usize,
+}
+
+impl ComposeSink for Counting {
+	fn write_str(&mut self, text: &str) {
+		for c in text.chars() {
+			if c == '\n' {
+				self.line += 1;
+				self.col = 1;
+			} else {
+				self.col += 1;
+			}
+		}
+		
+		self.out.push_str(text);
+	}
+}
+
+
+fn main() {
+	let dict = large_dict(50_000);
+	
+	println!("parsing {} KiB of dicts", dict.len() / 1024);
+	bench("parse_value (owned)", || parse_value(&dict).unwrap());
+	bench("parse_value_ref (borrowed)", || parse_value_ref(&dict).unwrap());
+	
+	// Non-ASCII chars in words have to be decoded, ASCII ones are looked up byte by byte.
+	// Borrowing keeps allocations from drowning out the difference.
+	let ascii = word_dict(50_000, &"value_number_".repeat(8));
+	let accented = word_dict(50_000, &"välue_nümbér_".repeat(8));
+	
+	println!();
+	println!("parsing dicts of long ASCII and non-ASCII words");
+	bench_throughput("parse_value_ref (ASCII)", ascii.len(), || parse_value_ref(&ascii).unwrap());
+	bench_throughput("parse_value_ref (non-ASCII)", accented.len(), || parse_value_ref(&accented).unwrap());
+	
+	let value = parse_value(&dict).unwrap();
+	let opts = ComposeOpts::PRETTY;
+	
+	println!();
+	println!("composing {} KiB of dicts", composed_len(&value, &opts).unwrap() / 1024);
+	bench("compose_value", || compose_value(&value, &opts).unwrap());
+	bench("compose_value_sized", || compose_value_sized(&value, &opts).unwrap());
+	
+	// What every compose paid before errors were located lazily
+	bench("compose_value_sink (counting chars)", || {
+		let mut sink = Counting {out: String::new(), line: 1, col: 1};
+		compose_value_sink(&value, &opts, &mut sink).unwrap();
+		sink
+	});
+	
+	let template = parse_value(&large_dict(5_000)).unwrap();
+	let mut scratch = template.clone();
+	
+	println!();
+	println!("refreshing a scratch value from a template 20 times");
+	bench("clone", || for _ in 0..20 {
+		scratch = black_box(&template).clone();
+	});
+	bench("clone_from", || for _ in 0..20 {
+		scratch.clone_from(black_box(&template));
+	});
+	
+	let words = word_list(10 << 20, 64);
+	
+	println!();
+	println!("parsing {} MiB of 64-char bare words", words.len() >> 20);
+	bench_throughput("parse_list", words.len(), || parse_list(&words).unwrap());
+	bench_throughput("parse_list_from_bytes", words.len(), || parse_list_from_bytes(words.as_bytes()).unwrap());
+	bench_throughput("parse_list_from (reader)", words.len(), || parse_list_from(words.as_bytes()).unwrap());
+}
@@ -0,0 +1,168 @@
+use std::{fmt, fmt::Write};
+
+use crate::{
+	JsefValue,
+	ComposeOpts,
+	compose_value, join_path,
+};
+
+
+/// Asserts that two [`JsefValue`]s are equal, like [`assert_eq!`].
+/// 
+/// On failure, the message names the first path at which the values differ
+/// and shows a line diff of both sides composed with [`ComposeOpts::PRETTY`]
+/// and sorted keys.
+#[macro_export]
+macro_rules! assert_jsef_eq {
+	( $left:expr, $right:expr $(,)? ) => {
+		match (&$left, &$right) {
+			(left, right) => if left != right {
+				$crate::__assert_jsef_failed(left, right, None);
+			},
+		}
+	};
+	
+	( $left:expr, $right:expr, $($arg:tt)+ ) => {
+		match (&$left, &$right) {
+			(left, right) => if left != right {
+				$crate::__assert_jsef_failed(left, right, Some(format_args!($($arg)+)));
+			},
+		}
+	};
+}
+
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_failed(left: &JsefValue, right: &JsefValue, args: Option<fmt::Arguments>) -> ! {
+	let path = first_difference(left, right, String::new()).unwrap_or_default();
+	let path = if path.is_empty() {"<root>"} else {&path};
+	
+	let mut msg = format!("JSeF values differ at {path}");
+	if let Some(args) = args {
+		let _ = write!(msg, ": {args}");
+	}
+	
+	let left = compose(left);
+	let right = compose(right);
+	msg.push_str("\n--- left\n+++ right\n");
+	line_diff(&mut msg, &left, &right);
+	
+	panic!("{msg}");
+}
+
+
+fn compose(value: &JsefValue) -> String {
+	compose_value(value, &ComposeOpts::PRETTY.sort_keys(true))
+		.unwrap_or_else(|_| format!("{value:#?}"))
+}
+
+/// Returns the path to the first differing value, sorting dict keys to keep it stable.
+fn first_difference(left: &JsefValue, right: &JsefValue, path: String) -> Option<String> {
+	match (left, right) {
+		(JsefValue::List(l), JsefValue::List(r)) => {
+			for i in 0..l.len().max(r.len()) {
+				let path = join_path(&path, &i.to_string());
+				
+				match (l.get(i), r.get(i)) {
+					(Some(l), Some(r)) => if let Some(p) = first_difference(l, r, path) {
+						return Some(p);
+					},
+					
+					_ => return Some(path),
+				}
+			}
+			
+			None
+		},
+		
+		(JsefValue::Dict(l), JsefValue::Dict(r)) => {
+			let mut keys: Vec<_> = l.keys().chain(r.keys()).collect();
+			keys.sort();
+			keys.dedup();
+			
+			for key in keys {
+				let path = join_path(&path, key);
+				
+				match (l.get(key), r.get(key)) {
+					(Some(l), Some(r)) => if let Some(p) = first_difference(l, r, path) {
+						return Some(p);
+					},
+					
+					_ => return Some(path),
+				}
+			}
+			
+			None
+		},
+		
+		(l, r) => (l != r).then_some(path),
+	}
+}
+
+/// The most cells the LCS table in [`line_diff`] may have before it stops looking for common lines.
+const MAX_DIFF_CELLS: usize = 1 << 20;
+
+/// Writes the lines of `left` and `right` to `out`, marking the ones missing on either side.
+/// 
+/// Past [`MAX_DIFF_CELLS`], the lines between the common prefix and suffix
+/// are written as removed and then added without aligning them.
+fn line_diff(out: &mut String, left: &str, right: &str) {
+	let left: Vec<_> = left.lines().collect();
+	let right: Vec<_> = right.lines().collect();
+	
+	let prefix = left.iter().zip(&right).take_while(|(l, r)| l == r).count();
+	let suffix = left[prefix..].iter().rev()
+		.zip(right[prefix..].iter().rev())
+		.take_while(|(l, r)| l == r)
+		.count();
+	
+	for line in &left[..prefix] {
+		let _ = writeln!(out, "  {line}");
+	}
+	
+	let (l_mid, r_mid) = (&left[prefix..left.len() - suffix], &right[prefix..right.len() - suffix]);
+	if l_mid.len().saturating_mul(r_mid.len()) > MAX_DIFF_CELLS {
+		for line in l_mid {
+			let _ = writeln!(out, "- {line}");
+		}
+		for line in r_mid {
+			let _ = writeln!(out, "+ {line}");
+		}
+	} else {
+		lcs_diff(out, l_mid, r_mid);
+	}
+	
+	for line in &left[left.len() - suffix..] {
+		let _ = writeln!(out, "  {line}");
+	}
+}
+
+fn lcs_diff(out: &mut String, left: &[&str], right: &[&str]) {
+	// lcs[i][j] is the length of the longest common subsequence of left[i..] and right[j..]
+	let mut lcs = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+	for i in (0..left.len()).rev() {
+		for j in (0..right.len()).rev() {
+			lcs[i][j] = if left[i] == right[j] {
+				lcs[i + 1][j + 1] + 1
+			} else {
+				lcs[i + 1][j].max(lcs[i][j + 1])
+			};
+		}
+	}
+	
+	let (mut i, mut j) = (0, 0);
+	while i < left.len() || j < right.len() {
+		if i < left.len() && j < right.len() && left[i] == right[j] {
+			let _ = writeln!(out, "  {}", left[i]);
+			i += 1;
+			j += 1;
+		} else if j == right.len() || (i < left.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+			let _ = writeln!(out, "- {}", left[i]);
+			i += 1;
+		} else {
+			let _ = writeln!(out, "+ {}", right[j]);
+			j += 1;
+		}
+	}
+}
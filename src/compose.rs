@@ -1,21 +1,26 @@
+use std::{iter, mem, fmt, error, io, str::FromStr};
+
 use crate::{
-	JsefValue, JsefList, JsefDict,
+	JsefValue, JsefList, JsefDict, JsefComments,
 	JsefErrType::{self, *},
 	JsefErr, JsefResult,
 	DEPTH_LIMIT,
-	is_word_char, count_line_col,
+	needs_quotes,
 };
 
 
 /// Formatting options for composing [`JsefValue`]s into strings.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ComposeOpts<'a> {
 	/// Used for indenting newlines.
 	/// `None` means the entire JSeF will be composed on a single line.
 	pub indent: Option<&'a str>,
 	
-	/// Whether all keys and values should be enclosed in double quotes regardless of their content.
-	pub force_quotes: bool,
+	/// Whether all keys should be enclosed in double quotes regardless of their content.
+	pub quote_keys: bool,
+	
+	/// Whether all string values should be enclosed in double quotes regardless of their content.
+	pub quote_values: bool,
 	
 	/// Whether extra spaces should be omitted when unnecessary.
 	pub dense: bool,
@@ -23,8 +28,66 @@ pub struct ComposeOpts<'a> {
 	/// Whether single-item dicts should be folded with the path notation.
 	pub fold_dicts: bool,
 	
+	/// Pairs nested less deeply than this, with the pairs of the outermost dict at depth 0, are never folded.
+	/// This is the same whether or not the root is enclosed in brackets.
+	/// Has no effect unless `fold_dicts` is set.
+	pub fold_dicts_from_depth: usize,
+	
+	/// Whether dict pairs should be composed in the sorted order of their keys
+	/// instead of the order the dict yields them in, at every nesting level.
+	/// Folded paths are sorted by their first key.
+	pub sort_keys: bool,
+	
 	/// A message that is written at the start of the composed string using line comments.
 	pub prelude: Option<&'a str>,
+	
+	/// Written at the start of every line of the `prelude`, which always begins at column 0.
+	/// Has to start a comment the parser accepts for the output to parse back.
+	pub prelude_marker: &'a str,
+	
+	/// Written before each comment passed to [`compose_dict_with_comments`](crate::compose_dict_with_comments).
+	/// Has to match [`ParseOpts::comment_lead`](crate::ParseOpts::comment_lead) for the output to parse back.
+	pub comment_lead: &'a str,
+	
+	/// Whether keys within a dict should be padded so their `=` signs line up.
+	/// Folded paths are left unaligned. Has no effect when `indent` is `None`.
+	pub align_equals: bool,
+	
+	/// Written between list items instead of a space when `indent` is `None`.
+	/// If it contains commas, the output has to be parsed with [`commas_ok`](crate::ParseOpts::commas_ok).
+	/// Composing fails with [`InvalidSeparator`](JsefErrType::InvalidSeparator) unless it's
+	/// non-empty and only made of ASCII whitespace and commas.
+	pub list_separator: Option<&'a str>,
+	
+	/// Whether only double quotes should be escaped in quoted strings,
+	/// matching [`ParseOpts::raw_strings`](crate::ParseOpts::raw_strings).
+	/// 
+	/// Quoted strings that end in a backslash can't be represented this way
+	/// and fail with [`TrailingBackslash`](JsefErrType::TrailingBackslash).
+	pub raw_strings: bool,
+	
+	/// Whether control chars without a short escape sequence should be written as `\u{...}` escapes
+	/// instead of as-is. Has no effect when `raw_strings` is set.
+	pub escape_controls: bool,
+	
+	/// Lists and dicts with at most this many items are composed on a single line
+	/// along with everything inside them, as if `indent` was `None`.
+	/// Root collections are never inlined.
+	pub inline_threshold: Option<usize>,
+	
+	/// A line break is written before the next list item or dict pair once a line is this many chars long.
+	/// Lines can still run past the limit, since breaks are only made between items.
+	/// Only applies when `indent` is `None`, collections inlined by `inline_threshold` aren't broken.
+	pub max_width: Option<usize>,
+	
+	/// Whether lists of dicts that all have the same keys and only string values
+	/// should be composed as a table, with one dict per line and their pairs aligned into columns.
+	/// Other lists are composed as usual. Has no effect when `indent` is `None`.
+	pub tabular: bool,
+	
+	/// Composing fails with [`OutputTooLong`](JsefErrType::OutputTooLong)
+	/// instead of writing more than this many bytes, the sink is left with a truncated output.
+	pub max_output_bytes: Option<usize>,
 }
 
 impl ComposeOpts<'static> {
@@ -32,51 +95,169 @@ impl ComposeOpts<'static> {
 	/// 
 	/// # Values
 	/// - `indent`: `Some("\t")`
-	/// - `force_quotes`: `false`
+	/// - `quote_keys`: `false`
+	/// - `quote_values`: `false`
 	/// - `dense`: `false`
 	/// - `fold_dicts`: `true`
+	/// - `fold_dicts_from_depth`: `0`
+	/// - `sort_keys`: `false`
 	/// - `prelude`: `None`
+	/// - `prelude_marker`: `"# "`
+	/// - `comment_lead`: `"#"`
+	/// - `align_equals`: `false`
+	/// - `list_separator`: `None`
+	/// - `raw_strings`: `false`
+	/// - `escape_controls`: `false`
+	/// - `inline_threshold`: `None`
+	/// - `max_width`: `None`
+	/// - `tabular`: `false`
+	/// - `max_output_bytes`: `None`
 	pub const PRETTY: Self = Self {
 		indent: Some("\t"),
-		force_quotes: false,
+		quote_keys: false,
+		quote_values: false,
 		dense: false,
 		fold_dicts: true,
+		fold_dicts_from_depth: 0,
+		sort_keys: false,
 		prelude: None,
+		prelude_marker: "# ",
+		comment_lead: "#",
+		align_equals: false,
+		list_separator: None,
+		raw_strings: false,
+		escape_controls: false,
+		inline_threshold: None,
+		max_width: None,
+		tabular: false,
+		max_output_bytes: None,
 	};
 	
 	/// The default options for compact outputs not necessarily intended for reading.
 	/// 
 	/// # Values
 	/// - `indent`: `None`
-	/// - `force_quotes`: `false`
+	/// - `quote_keys`: `false`
+	/// - `quote_values`: `false`
 	/// - `dense`: `true`
 	/// - `fold_dicts`: `true`
+	/// - `fold_dicts_from_depth`: `0`
+	/// - `sort_keys`: `false`
 	/// - `prelude`: `None`
+	/// - `prelude_marker`: `"# "`
+	/// - `comment_lead`: `"#"`
+	/// - `align_equals`: `false`
+	/// - `list_separator`: `None`
+	/// - `raw_strings`: `false`
+	/// - `escape_controls`: `false`
+	/// - `inline_threshold`: `None`
+	/// - `max_width`: `None`
+	/// - `tabular`: `false`
+	/// - `max_output_bytes`: `None`
 	pub const COMPACT: Self = Self {
 		indent: None,
-		force_quotes: false,
+		quote_keys: false,
+		quote_values: false,
 		dense: true,
 		fold_dicts: true,
+		fold_dicts_from_depth: 0,
+		sort_keys: false,
 		prelude: None,
+		prelude_marker: "# ",
+		comment_lead: "#",
+		align_equals: false,
+		list_separator: None,
+		raw_strings: false,
+		escape_controls: false,
+		inline_threshold: None,
+		max_width: None,
+		tabular: false,
+		max_output_bytes: None,
 	};
 	
 	/// The default options for simplified outputs that are easier to parse.
 	/// 
 	/// # Values
 	/// - `indent`: `None`
-	/// - `force_quotes`: `true`
+	/// - `quote_keys`: `true`
+	/// - `quote_values`: `true`
 	/// - `dense`: `true`
 	/// - `fold_dicts`: `false`
+	/// - `fold_dicts_from_depth`: `0`
+	/// - `sort_keys`: `false`
 	/// - `prelude`: `None`
+	/// - `prelude_marker`: `"# "`
+	/// - `comment_lead`: `"#"`
+	/// - `align_equals`: `false`
+	/// - `list_separator`: `None`
+	/// - `raw_strings`: `false`
+	/// - `escape_controls`: `false`
+	/// - `inline_threshold`: `None`
+	/// - `max_width`: `None`
+	/// - `tabular`: `false`
+	/// - `max_output_bytes`: `None`
 	pub const SIMPLE: Self = Self {
 		indent: None,
-		force_quotes: true,
+		quote_keys: true,
+		quote_values: true,
 		dense: true,
 		fold_dicts: false,
+		fold_dicts_from_depth: 0,
+		sort_keys: false,
 		prelude: None,
+		prelude_marker: "# ",
+		comment_lead: "#",
+		align_equals: false,
+		list_separator: None,
+		raw_strings: false,
+		escape_controls: false,
+		inline_threshold: None,
+		max_width: None,
+		tabular: false,
+		max_output_bytes: None,
 	};
 }
 
+impl FromStr for ComposeOpts<'static> {
+	type Err = UnknownPreset;
+	
+	/// Looks up a preset by its lowercase name, like `"pretty"` for [`PRETTY`](Self::PRETTY).
+	/// Case is ignored.
+	fn from_str(name: &str) -> Result<Self, UnknownPreset> {
+		PRESETS.iter()
+			.find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+			.map(|(_, opts)| opts.clone())
+			.ok_or_else(|| UnknownPreset(name.to_owned()))
+	}
+}
+
+const PRESETS: [(&str, ComposeOpts); 3] = [
+	("pretty", ComposeOpts::PRETTY),
+	("compact", ComposeOpts::COMPACT),
+	("simple", ComposeOpts::SIMPLE),
+];
+
+
+/// The error returned when parsing [`ComposeOpts`] from an unknown preset name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPreset(pub String);
+
+impl fmt::Display for UnknownPreset {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "unknown compose preset \"{}\", expected one of: ", self.0)?;
+		
+		for (i, (name, _)) in PRESETS.iter().enumerate() {
+			if i > 0 {write!(f, ", ")?;}
+			write!(f, "{name}")?;
+		}
+		
+		Ok(())
+	}
+}
+
+impl error::Error for UnknownPreset {}
+
+
 impl<'a> ComposeOpts<'a> {
 	pub const fn indent(mut self, value: &'a str) -> Self {
 		self.indent = Some(value);
@@ -88,8 +269,20 @@ impl<'a> ComposeOpts<'a> {
 		self
 	}
 	
+	/// Sets both [`quote_keys`](Self::quote_keys) and [`quote_values`](Self::quote_values).
 	pub const fn force_quotes(mut self, value: bool) -> Self {
-		self.force_quotes = value;
+		self.quote_keys = value;
+		self.quote_values = value;
+		self
+	}
+	
+	pub const fn quote_keys(mut self, value: bool) -> Self {
+		self.quote_keys = value;
+		self
+	}
+	
+	pub const fn quote_values(mut self, value: bool) -> Self {
+		self.quote_values = value;
 		self
 	}
 	
@@ -103,6 +296,16 @@ impl<'a> ComposeOpts<'a> {
 		self
 	}
 	
+	pub const fn fold_dicts_from_depth(mut self, value: usize) -> Self {
+		self.fold_dicts_from_depth = value;
+		self
+	}
+	
+	pub const fn sort_keys(mut self, value: bool) -> Self {
+		self.sort_keys = value;
+		self
+	}
+	
 	pub const fn prelude(mut self, value: &'a str) -> Self {
 		self.prelude = Some(value);
 		self
@@ -112,145 +315,854 @@ impl<'a> ComposeOpts<'a> {
 		self.prelude = None;
 		self
 	}
+	
+	pub const fn prelude_marker(mut self, value: &'a str) -> Self {
+		self.prelude_marker = value;
+		self
+	}
+	
+	pub const fn comment_lead(mut self, value: &'a str) -> Self {
+		self.comment_lead = value;
+		self
+	}
+	
+	pub const fn align_equals(mut self, value: bool) -> Self {
+		self.align_equals = value;
+		self
+	}
+	
+	pub const fn list_separator(mut self, value: &'a str) -> Self {
+		self.list_separator = Some(value);
+		self
+	}
+	
+	pub const fn no_list_separator(mut self) -> Self {
+		self.list_separator = None;
+		self
+	}
+	
+	pub const fn raw_strings(mut self, value: bool) -> Self {
+		self.raw_strings = value;
+		self
+	}
+	
+	pub const fn escape_controls(mut self, value: bool) -> Self {
+		self.escape_controls = value;
+		self
+	}
+	
+	pub const fn inline_threshold(mut self, value: usize) -> Self {
+		self.inline_threshold = Some(value);
+		self
+	}
+	
+	pub const fn no_inline_threshold(mut self) -> Self {
+		self.inline_threshold = None;
+		self
+	}
+	
+	pub const fn max_width(mut self, value: usize) -> Self {
+		self.max_width = Some(value);
+		self
+	}
+	
+	pub const fn no_max_width(mut self) -> Self {
+		self.max_width = None;
+		self
+	}
+	
+	pub const fn tabular(mut self, value: bool) -> Self {
+		self.tabular = value;
+		self
+	}
+	
+	pub const fn max_output_bytes(mut self, value: usize) -> Self {
+		self.max_output_bytes = Some(value);
+		self
+	}
+	
+	pub const fn no_max_output_bytes(mut self) -> Self {
+		self.max_output_bytes = None;
+		self
+	}
+}
+
+
+/// An owned copy of [`ComposeOpts`] that can be stored without borrowing its strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ComposeOptsOwned {
+	pub indent: Option<String>,
+	pub quote_keys: bool,
+	pub quote_values: bool,
+	pub dense: bool,
+	pub fold_dicts: bool,
+	pub fold_dicts_from_depth: usize,
+	pub sort_keys: bool,
+	pub prelude: Option<String>,
+	pub prelude_marker: String,
+	pub comment_lead: String,
+	pub align_equals: bool,
+	pub list_separator: Option<String>,
+	pub raw_strings: bool,
+	pub escape_controls: bool,
+	pub inline_threshold: Option<usize>,
+	pub max_width: Option<usize>,
+	pub tabular: bool,
+	pub max_output_bytes: Option<usize>,
+}
+
+impl ComposeOptsOwned {
+	/// Borrows these options as [`ComposeOpts`] for passing them to the compose functions.
+	pub fn as_opts(&self) -> ComposeOpts<'_> {
+		ComposeOpts {
+			indent: self.indent.as_deref(),
+			quote_keys: self.quote_keys,
+			quote_values: self.quote_values,
+			dense: self.dense,
+			fold_dicts: self.fold_dicts,
+			fold_dicts_from_depth: self.fold_dicts_from_depth,
+			sort_keys: self.sort_keys,
+			prelude: self.prelude.as_deref(),
+			prelude_marker: &self.prelude_marker,
+			comment_lead: &self.comment_lead,
+			align_equals: self.align_equals,
+			list_separator: self.list_separator.as_deref(),
+			raw_strings: self.raw_strings,
+			escape_controls: self.escape_controls,
+			inline_threshold: self.inline_threshold,
+			max_width: self.max_width,
+			tabular: self.tabular,
+			max_output_bytes: self.max_output_bytes,
+		}
+	}
+	
+	pub fn indent(mut self, value: impl Into<String>) -> Self {
+		self.indent = Some(value.into());
+		self
+	}
+	
+	pub fn no_indent(mut self) -> Self {
+		self.indent = None;
+		self
+	}
+	
+	/// Sets both [`quote_keys`](Self::quote_keys) and [`quote_values`](Self::quote_values).
+	pub fn force_quotes(mut self, value: bool) -> Self {
+		self.quote_keys = value;
+		self.quote_values = value;
+		self
+	}
+	
+	pub fn quote_keys(mut self, value: bool) -> Self {
+		self.quote_keys = value;
+		self
+	}
+	
+	pub fn quote_values(mut self, value: bool) -> Self {
+		self.quote_values = value;
+		self
+	}
+	
+	pub fn dense(mut self, value: bool) -> Self {
+		self.dense = value;
+		self
+	}
+	
+	pub fn fold_dicts(mut self, value: bool) -> Self {
+		self.fold_dicts = value;
+		self
+	}
+	
+	pub fn fold_dicts_from_depth(mut self, value: usize) -> Self {
+		self.fold_dicts_from_depth = value;
+		self
+	}
+	
+	pub fn sort_keys(mut self, value: bool) -> Self {
+		self.sort_keys = value;
+		self
+	}
+	
+	pub fn prelude(mut self, value: impl Into<String>) -> Self {
+		self.prelude = Some(value.into());
+		self
+	}
+	
+	pub fn no_prelude(mut self) -> Self {
+		self.prelude = None;
+		self
+	}
+	
+	pub fn prelude_marker(mut self, value: impl Into<String>) -> Self {
+		self.prelude_marker = value.into();
+		self
+	}
+	
+	pub fn comment_lead(mut self, value: impl Into<String>) -> Self {
+		self.comment_lead = value.into();
+		self
+	}
+	
+	pub fn align_equals(mut self, value: bool) -> Self {
+		self.align_equals = value;
+		self
+	}
+	
+	pub fn list_separator(mut self, value: impl Into<String>) -> Self {
+		self.list_separator = Some(value.into());
+		self
+	}
+	
+	pub fn no_list_separator(mut self) -> Self {
+		self.list_separator = None;
+		self
+	}
+	
+	pub fn raw_strings(mut self, value: bool) -> Self {
+		self.raw_strings = value;
+		self
+	}
+	
+	pub fn escape_controls(mut self, value: bool) -> Self {
+		self.escape_controls = value;
+		self
+	}
+	
+	pub fn inline_threshold(mut self, value: usize) -> Self {
+		self.inline_threshold = Some(value);
+		self
+	}
+	
+	pub fn no_inline_threshold(mut self) -> Self {
+		self.inline_threshold = None;
+		self
+	}
+	
+	pub fn max_width(mut self, value: usize) -> Self {
+		self.max_width = Some(value);
+		self
+	}
+	
+	pub fn no_max_width(mut self) -> Self {
+		self.max_width = None;
+		self
+	}
+	
+	pub fn tabular(mut self, value: bool) -> Self {
+		self.tabular = value;
+		self
+	}
+	
+	pub fn max_output_bytes(mut self, value: usize) -> Self {
+		self.max_output_bytes = Some(value);
+		self
+	}
+	
+	pub fn no_max_output_bytes(mut self) -> Self {
+		self.max_output_bytes = None;
+		self
+	}
+}
+
+impl From<&ComposeOpts<'_>> for ComposeOptsOwned {
+	fn from(opts: &ComposeOpts) -> Self {
+		Self {
+			indent: opts.indent.map(str::to_owned),
+			quote_keys: opts.quote_keys,
+			quote_values: opts.quote_values,
+			dense: opts.dense,
+			fold_dicts: opts.fold_dicts,
+			fold_dicts_from_depth: opts.fold_dicts_from_depth,
+			sort_keys: opts.sort_keys,
+			prelude: opts.prelude.map(str::to_owned),
+			prelude_marker: opts.prelude_marker.to_owned(),
+			comment_lead: opts.comment_lead.to_owned(),
+			align_equals: opts.align_equals,
+			list_separator: opts.list_separator.map(str::to_owned),
+			raw_strings: opts.raw_strings,
+			escape_controls: opts.escape_controls,
+			inline_threshold: opts.inline_threshold,
+			max_width: opts.max_width,
+			tabular: opts.tabular,
+			max_output_bytes: opts.max_output_bytes,
+		}
+	}
+}
+
+impl From<ComposeOpts<'_>> for ComposeOptsOwned {
+	fn from(opts: ComposeOpts) -> Self {
+		Self::from(&opts)
+	}
+}
+
+
+/// Receives the output of the composer token by token.
+/// 
+/// Each callback gets the exact text of its token, already quoted and escaped,
+/// and passes it on to [`write_str`](Self::write_str) by default.
+/// Overriding some of them allows decorating the output, for example with syntax highlighting.
+pub trait ComposeSink {
+	/// Writes `text` as-is. All other callbacks end up here unless overridden.
+	fn write_str(&mut self, text: &str);
+	
+	/// Hints that at least `additional` more bytes are about to be written.
+	fn reserve(&mut self, _additional: usize) {}
+	
+	/// A line of the prelude, including the leading [`prelude_marker`](ComposeOpts::prelude_marker)
+	/// and the trailing newline, or an attached comment including its [`comment_lead`](ComposeOpts::comment_lead).
+	fn comment(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// The opening `{` of a dict or `[` of a list.
+	fn enter(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// The closing `}` of a dict or `]` of a list.
+	fn exit(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// A dict key, or a single segment of a folded path.
+	fn key(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// The `.` between the segments of a folded path.
+	fn dot(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// The `=` between a key and its value, along with any surrounding spaces.
+	fn equals(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// A string value.
+	fn scalar(&mut self, text: &str) {
+		self.write_str(text);
+	}
+	
+	/// Whitespace between tokens, which includes newlines, indents,
+	/// alignment padding and [`list_separator`](ComposeOpts::list_separator)s.
+	fn separator(&mut self, text: &str) {
+		self.write_str(text);
+	}
+}
+
+impl ComposeSink for String {
+	fn write_str(&mut self, text: &str) {
+		self.push_str(text);
+	}
+	
+	fn reserve(&mut self, additional: usize) {
+		String::reserve(self, additional);
+	}
+}
+
+impl<S: ComposeSink + ?Sized> ComposeSink for &mut S {
+	fn write_str(&mut self, text: &str) {(**self).write_str(text)}
+	fn reserve(&mut self, additional: usize) {(**self).reserve(additional)}
+	fn comment(&mut self, text: &str) {(**self).comment(text)}
+	fn enter(&mut self, text: &str) {(**self).enter(text)}
+	fn exit(&mut self, text: &str) {(**self).exit(text)}
+	fn key(&mut self, text: &str) {(**self).key(text)}
+	fn dot(&mut self, text: &str) {(**self).dot(text)}
+	fn equals(&mut self, text: &str) {(**self).equals(text)}
+	fn scalar(&mut self, text: &str) {(**self).scalar(text)}
+	fn separator(&mut self, text: &str) {(**self).separator(text)}
+}
+
+
+/// A [`ComposeSink`] that only keeps track of the composed length.
+#[derive(Debug)]
+pub(crate) struct LenCounter {
+	pub(crate) len: usize,
+}
+
+impl LenCounter {
+	pub(crate) fn new() -> Self {
+		Self {len: 0}
+	}
+}
+
+impl ComposeSink for LenCounter {
+	fn write_str(&mut self, text: &str) {
+		self.len += text.len();
+	}
+}
+
+
+/// A [`ComposeSink`] that passes everything on to an [`io::Write`].
+/// 
+/// Sink callbacks can't fail, so the first IO error is kept and all writes after it are dropped.
+#[derive(Debug)]
+pub(crate) struct WriteSink<W> {
+	writer: W,
+	/// Number of bytes written before the first error.
+	written: usize,
+	err: Option<io::Error>,
+}
+
+impl<W: io::Write> WriteSink<W> {
+	pub(crate) fn new(writer: W) -> Self {
+		Self {writer, written: 0, err: None}
+	}
+	
+	/// Flushes the writer, then turns a kept IO error into a [`JsefErr`],
+	/// positioned by `locate` given the number of bytes written before it.
+	fn finish(mut self, locate: impl FnOnce(usize) -> (usize, usize)) -> JsefResult {
+		if self.err.is_none() {
+			self.err = self.writer.flush().err();
+		}
+		
+		let Some(err) = self.err else {return Ok(())};
+		
+		let (line, col) = locate(self.written);
+		Err(JsefErr::new(Io(err.kind()), line, col, self.written))
+	}
+}
+
+impl<W: io::Write> ComposeSink for WriteSink<W> {
+	fn write_str(&mut self, text: &str) {
+		if self.err.is_some() {
+			return;
+		}
+		
+		match self.writer.write_all(text.as_bytes()) {
+			Ok(()) => self.written += text.len(),
+			Err(e) => self.err = Some(e),
+		}
+	}
+}
+
+
+/// A [`ComposeSink`] that only keeps track of the position the next write would start at.
+#[derive(Debug)]
+struct PosCounter {
+	line: usize,
+	col: usize,
+	byte: usize,
+}
+
+impl ComposeSink for PosCounter {
+	fn write_str(&mut self, text: &str) {
+		self.byte += text.len();
+		
+		for c in text.chars() {
+			if c == '\n' {
+				self.line += 1;
+				self.col = 1;
+			} else {
+				self.col += 1;
+			}
+		}
+	}
+}
+
+
+#[derive(Debug, Clone, Copy)]
+enum Root<'v> {
+	Value(&'v JsefValue),
+	List(&'v JsefList),
+	Dict(&'v JsefDict),
 }
 
 
 #[derive(Debug)]
-pub(crate) struct Composer<'o> {
+pub(crate) struct Composer<'o, S> {
 	opts: &'o ComposeOpts<'o>,
-	target: String,
+	sink: S,
 	depth: usize,
+	/// The depth of the items of the root, which is 1 once its brackets are written.
+	root_depth: usize,
+	/// Whether a collection that is being composed on a single line was entered.
+	inline: bool,
+	/// Reused for tokens that don't exist as a single string ahead of time.
+	buf: String,
+	/// Number of bytes passed to the sink so far.
+	written: usize,
+	/// Whether a write was dropped for exceeding [`max_output_bytes`](ComposeOpts::max_output_bytes).
+	overflowed: bool,
+	/// Number of chars written since the last newline, only counted with [`max_width`](ComposeOpts::max_width).
+	col: usize,
+	/// Comments to write before the values they're attached to.
+	comments: Option<&'o JsefComments>,
+	/// The dotted path of the value being composed, only kept track of when writing comments.
+	path: String,
 }
 
-impl<'o> Composer<'o> {
-	pub(crate) fn new(opts: &'o ComposeOpts) -> Self {
-		Self {target: String::new(), depth: 0, opts}
+impl<'o, S: ComposeSink> Composer<'o, S> {
+	pub(crate) fn new(opts: &'o ComposeOpts, sink: S) -> Self {
+		Self {
+			sink, depth: 0, root_depth: 0, opts,
+			inline: false,
+			buf: String::new(),
+			written: 0,
+			overflowed: false,
+			col: 0,
+			comments: None,
+			path: String::new(),
+		}
 	}
 	
-	pub(crate) fn compose_value_root(mut self, value: &JsefValue) -> JsefResult<String> {
-		self.compose_prelude();
-		self.compose_value(value)?;
-		Ok(self.target)
+	pub(crate) fn with_comments(mut self, comments: &'o JsefComments) -> Self {
+		self.comments = Some(comments);
+		self
 	}
 	
-	pub(crate) fn compose_list_root(mut self, list: &JsefList) -> JsefResult<String> {
-		self.compose_prelude();
-		self.compose_list(list, true)?;
-		Ok(self.target)
+	pub(crate) fn compose_value_root(self, value: &JsefValue) -> JsefResult<S> {
+		self.finish(Root::Value(value))
 	}
 	
-	pub(crate) fn compose_dict_root(mut self, dict: &JsefDict) -> JsefResult<String> {
-		self.compose_prelude();
-		self.compose_dict(dict, true)?;
-		Ok(self.target)
+	pub(crate) fn compose_list_root(self, list: &JsefList) -> JsefResult<S> {
+		self.finish(Root::List(list))
+	}
+	
+	pub(crate) fn compose_dict_root(self, dict: &JsefDict) -> JsefResult<S> {
+		self.finish(Root::Dict(dict))
+	}
+	
+	fn finish(mut self, root: Root) -> JsefResult<S> {
+		self.check_opts()?;
+		
+		match self.compose_root(root).and_then(|()| self.check_output()) {
+			Ok(()) => Ok(self.sink),
+			Err(err) => Err(locate(self.opts, self.comments, root, err.err)),
+		}
+	}
+}
+
+impl<'o, W: io::Write> Composer<'o, WriteSink<W>> {
+	pub(crate) fn write_value_root(self, value: &JsefValue) -> JsefResult {
+		self.write(Root::Value(value))
+	}
+	
+	pub(crate) fn write_list_root(self, list: &JsefList) -> JsefResult {
+		self.write(Root::List(list))
+	}
+	
+	pub(crate) fn write_dict_root(self, dict: &JsefDict) -> JsefResult {
+		self.write(Root::Dict(dict))
+	}
+	
+	fn write(self, root: Root) -> JsefResult {
+		let (opts, comments) = (self.opts, self.comments);
+		self.finish(root)?.finish(|written| locate_byte(opts, comments, root, written))
 	}
 }
 
-impl Composer<'_> {
+/// Finds the position of an error that occurred while composing `root`.
+/// 
+/// The composer doesn't keep track of positions itself, since errors are rare
+/// and counting every char would slow composition down for nothing.
+/// Composing is deterministic, so doing it again runs into the same error at the same spot.
+fn locate(opts: &ComposeOpts, comments: Option<&JsefComments>, root: Root, err: JsefErrType) -> JsefErr {
+	let mut composer = Composer::new(opts, PosCounter {line: 1, col: 1, byte: 0});
+	composer.comments = comments;
+	let _ = composer.compose_root(root);
+	
+	JsefErr::new(err, composer.sink.line, composer.sink.col, composer.sink.byte)
+}
+
+/// Finds the line and col that composing `root` reaches after `byte` bytes, like [`locate`].
+/// 
+/// Composing stops right there, so nothing past `byte` is composed again.
+/// Tokens are written whole, so `byte` always falls between two of them.
+fn locate_byte(opts: &ComposeOpts, comments: Option<&JsefComments>, root: Root, byte: usize) -> (usize, usize) {
+	let opts = opts.clone().max_output_bytes(byte);
+	let mut composer = Composer::new(&opts, PosCounter {line: 1, col: 1, byte: 0});
+	composer.comments = comments;
+	let _ = composer.compose_root(root);
+	
+	(composer.sink.line, composer.sink.col)
+}
+
+impl<'o, S: ComposeSink> Composer<'o, S> {
+	/// Creates an error whose position is filled in by [`locate`] later.
 	fn err(&self, err: JsefErrType) -> JsefErr {
-		let (line, col) = count_line_col(&self.target);
-		JsefErr::new(err, line, col)
+		JsefErr::new(err, 0, 0, 0)
+	}
+	
+	fn emit(&mut self, text: &str, func: fn(&mut S, &str)) {
+		if self.overflowed {
+			return;
+		}
+		
+		let written = self.written + text.len();
+		if self.opts.max_output_bytes.is_some_and(|max| written > max) {
+			self.overflowed = true;
+			return;
+		}
+		
+		self.written = written;
+		func(&mut self.sink, text);
+		
+		if self.opts.max_width.is_some() {
+			self.col = match text.rsplit_once('\n') {
+				Some((_, last)) => last.chars().count(),
+				None => self.col + text.chars().count(),
+			};
+		}
+	}
+	
+	/// Fails once a write was dropped by [`Self::emit`], so composition stops early.
+	/// Rejects options that would produce output that doesn't parse back.
+	fn check_opts(&self) -> JsefResult {
+		match self.opts.list_separator {
+			Some(sep) if sep.is_empty() || !sep.chars().all(|c| c.is_ascii_whitespace() || c == ',') =>
+				Err(JsefErr::new(InvalidSeparator(sep.to_owned()), 0, 0, 0)),
+			_ => Ok(()),
+		}
+	}
+	
+	fn check_output(&self) -> JsefResult {
+		match self.opts.max_output_bytes {
+			Some(max) if self.overflowed => Err(self.err(OutputTooLong(max))),
+			_ => Ok(()),
+		}
+	}
+	
+	/// Clears the reused buffer, fills it with `fill` and emits it using `func`.
+	fn emit_buf(&mut self, func: fn(&mut S, &str), fill: impl FnOnce(&Self, &mut String)) {
+		let mut buf = mem::take(&mut self.buf);
+		buf.clear();
+		fill(self, &mut buf);
+		self.emit(&buf, func);
+		self.buf = buf;
+	}
+	
+	/// Returns whether a collection with `len` items would be composed on a single line.
+	fn inlines(&self, len: usize, root: bool) -> bool {
+		self.inline || (!root && self.opts.inline_threshold.is_some_and(|max| len <= max))
+	}
+	
+	/// Returns the indent to use at the current position, which is `None` within inlined collections.
+	fn indent(&self) -> Option<&'o str> {
+		if self.inline {None} else {self.opts.indent}
 	}
 	
 	fn separator(&mut self, space: bool) {
-		if let Some(indent) = self.opts.indent {
+		if let Some(indent) = self.indent() {
 			let len = indent.len() * self.depth + 1;
-			self.target.reserve(len);
-			self.target.push_str("\n");
+			self.sink.reserve(len);
 			
-			for _ in 0..self.depth {
-				self.target.push_str(indent);
-			}
+			let depth = self.depth;
+			self.emit_buf(S::separator, |_, buf| {
+				buf.push('\n');
+				for _ in 0..depth {
+					buf.push_str(indent);
+				}
+			});
 		} else if space || !self.opts.dense {
-			self.target.push_str(" ");
+			self.emit(" ", S::separator);
+		}
+	}
+	
+	fn item_separator(&mut self, sep: Option<&str>) {
+		if self.opts.indent.is_none() && self.opts.max_width.is_some_and(|max| self.col >= max) {
+			// Trailing spaces of the separator would only end up at the end of the line
+			let sep = sep.map_or("", str::trim_end);
+			self.emit_buf(S::separator, |_, buf| {
+				buf.push_str(sep);
+				buf.push('\n');
+			});
+			
+			return;
+		}
+		
+		match (self.indent(), sep) {
+			(None, Some(sep)) => self.emit(sep, S::separator),
+			_ => self.separator(true),
 		}
 	}
 	
 	fn compose_prelude(&mut self) {
 		if let Some(msg) = self.opts.prelude {
+			let marker = self.opts.prelude_marker;
+			
 			for line in msg.lines() {
-				self.target.push_str("# ");
-				self.target.push_str(line);
-				self.target.push_str("\n");
+				self.emit_buf(S::comment, |_, buf| {
+					buf.push_str(marker);
+					buf.push_str(line);
+					buf.push('\n');
+				});
 			}
 		}
 	}
 	
-	fn escape_string(&mut self, string: &str) {
+	fn escape(&self, c: char) -> Option<&'static str> {
+		if self.opts.raw_strings {
+			(c == '"').then_some("\\\"")
+		} else if self.opts.escape_controls && c < ' ' {
+			escape_char(c).or(Some(CONTROL_ESCAPES[c as usize]))
+		} else {
+			escape_char(c)
+		}
+	}
+	
+	fn escape_string(&self, buf: &mut String, string: &str) {
 		let mut idx = 0;
 		for (i, c) in string.char_indices() {
-			let esc = match c {
-				'\n' => "\\n",
-				'\t' => "\\t",
-				'\r' => "\\r",
-				'\0' => "\\0",
-				'\\' => "\\\\",
-				'"' => "\\\"",
-				
-				_ => continue,
-			};
+			let Some(esc) = self.escape(c) else {continue};
 			
 			let slice = &string[idx..i];
-			self.target.push_str(slice);
-			self.target.push_str(esc);
+			buf.push_str(slice);
+			buf.push_str(esc);
 			
-			// CHANGE THIS if escaped chars can be more than one byte long
-			idx = i + 1;
+			idx = i + c.len_utf8();
 		}
 		
 		let slice = &string[idx..];
-		self.target.push_str(slice);
+		buf.push_str(slice);
 	}
 	
-	fn compose_string(&mut self, string: &str) {
-		let quotes = self.opts.force_quotes ||
-			string.chars().any(|c| !is_word_char(c));
+	fn quotes(&self, string: &str, key: bool) -> bool {
+		let commas = self.opts.list_separator.is_some_and(|sep| sep.contains(','));
+		let forced = if key {self.opts.quote_keys} else {self.opts.quote_values};
 		
-		if quotes {
-			self.target.push_str("\"");
-			self.escape_string(string);
-			self.target.push_str("\"");
+		forced ||
+			needs_quotes(string) ||
+			(commas && string.contains(','))
+	}
+	
+	/// Returns the number of chars [`Self::compose_string`] would write for `string`.
+	fn string_width(&self, string: &str, key: bool) -> usize {
+		if self.quotes(string, key) {
+			let escaped: usize = string.chars()
+				.map(|c| self.escape(c).map_or(1, str::len))
+				.sum();
+			
+			escaped + 2
 		} else {
-			self.escape_string(string);
+			string.chars().count()
 		}
 	}
 	
-	fn compose_pair(&mut self, key: &str, mut value: &JsefValue) -> JsefResult {
-		self.compose_string(key);
+	fn compose_string(&mut self, string: &str, key: bool) -> JsefResult {
+		let func = if key {S::key} else {S::scalar};
 		
-		if self.opts.fold_dicts {
-			while let Some(dict) = value.as_dict() {
-				if dict.len() != 1 {break;}
-				
-				// dict.len() == 1 here, so unwrap should be ok
-				let (key, val) = dict.iter().next().unwrap();
-				self.target.push_str(".");
-				self.target.push_str(key);
-				value = val;
+		if self.quotes(string, key) {
+			if self.opts.raw_strings && string.ends_with('\\') {
+				return Err(self.err(TrailingBackslash));
 			}
+			
+			self.emit_buf(func, |this, buf| {
+				buf.push('"');
+				this.escape_string(buf, string);
+				buf.push('"');
+			});
+		} else {
+			self.emit(string, func);
+		}
+		
+		Ok(())
+	}
+	
+	/// Returns whether pairs at the current depth are folded at all.
+	fn folding(&self) -> bool {
+		self.opts.fold_dicts && self.depth - self.root_depth >= self.opts.fold_dicts_from_depth
+	}
+	
+	fn folds(&self, value: &JsefValue) -> bool {
+		self.folding() && value.as_dict().is_some_and(|d| d.len() == 1)
+	}
+	
+	/// Returns the width unfolded keys of `dict` should be padded to,
+	/// or 0 if they shouldn't be aligned.
+	fn align_width(&self, dict: &JsefDict) -> usize {
+		if !self.opts.align_equals || self.indent().is_none() {
+			return 0;
+		}
+		
+		dict.iter()
+			.filter(|(_, val)| !self.folds(val))
+			.map(|(key, _)| self.string_width(key, true))
+			.max()
+			.unwrap_or(0)
+	}
+	
+	fn compose_pair(&mut self, key: &str, value: &JsefValue, align: usize) -> JsefResult {
+		let (path, value) = if self.folding() {
+			fold_path(value)
+		} else {
+			(Vec::new(), value)
+		};
+		
+		let base = self.path.len();
+		if self.comments.is_some() {
+			for key in iter::once(key).chain(path.iter().copied()) {
+				self.push_path(key);
+				self.compose_comments();
+			}
+		}
+		
+		self.compose_string(key, true)?;
+		
+		let width = self.string_width(key, true);
+		if path.is_empty() && width < align {
+			self.emit_buf(S::separator, |_, buf| {
+				buf.extend(iter::repeat_n(' ', align - width));
+			});
+		}
+		
+		for key in path {
+			self.emit(".", S::dot);
+			self.compose_string(key, true)?;
 		}
 		
 		if self.opts.dense {
-			self.target.push_str("=");
+			self.emit("=", S::equals);
 		} else {
-			self.target.push_str(" = ");
+			self.emit(" = ", S::equals);
 		}
 		
 		self.compose_value(value)?;
+		self.path.truncate(base);
 		Ok(())
 	}
 	
+	fn push_path(&mut self, key: &str) {
+		if !self.path.is_empty() {
+			self.path.push('.');
+		}
+		
+		self.path.push_str(key);
+	}
+	
+	/// Writes the comments attached to the current path, each on its own line.
+	/// Comments are left out within single-line output, since they'd swallow the rest of the line.
+	fn compose_comments(&mut self) {
+		let Some(comments) = self.comments else {return};
+		let Some(lines) = comments.get(&self.path) else {return};
+		
+		if self.indent().is_none() {
+			return;
+		}
+		
+		for line in lines {
+			self.emit_buf(S::comment, |this, buf| {
+				buf.push_str(this.opts.comment_lead);
+				buf.push_str(line);
+			});
+			
+			self.separator(true);
+		}
+	}
+	
 	fn compose_many<I, F>(
 		&mut self,
 		root: bool,
-		open: char, close: char,
+		open: &str, close: &str, sep: Option<&str>,
 		mut iter: I, mut func: F
 	) -> JsefResult
 	where
-		I: Iterator,
+		I: ExactSizeIterator,
 		F: FnMut(&mut Self, I::Item) -> JsefResult,
 	{
 		let mut empty = true;
+		let inline = self.inline;
 		
 		if !root {
 			self.depth += 1;
@@ -258,46 +1170,200 @@ impl Composer<'_> {
 				return Err(self.err(MaxDepth));
 			}
 			
-			self.target.push(open);
+			self.inline = self.inlines(iter.len(), root);
+			self.emit(open, S::enter);
 		}
 		
 		if let Some(it) = iter.next() {
 			empty = false;
 			if !root {self.separator(false);}
 			func(self, it)?;
+			self.check_output()?;
 		}
 		
 		for it in iter {
-			self.separator(true);
+			self.item_separator(sep);
 			func(self, it)?;
+			self.check_output()?;
 		}
 		
 		if !root {
 			self.depth -= 1;
 			if !empty {self.separator(false);}
-			self.target.push(close);
+			self.emit(close, S::exit);
+			self.inline = inline;
 		}
 		
 		Ok(())
 	}
 	
+	fn compose_root(&mut self, root: Root) -> JsefResult {
+		self.compose_prelude();
+		
+		match root {
+			Root::Value(value) => {
+				self.root_depth = if value.is_string() {0} else {1};
+				self.compose_value(value)
+			},
+			
+			Root::List(list) => self.compose_list(list, true),
+			Root::Dict(dict) => self.compose_dict(dict, true),
+		}
+	}
+	
 	fn compose_value(&mut self, value: &JsefValue) -> JsefResult {
 		match value {
-			JsefValue::String(string) => Ok(self.compose_string(string)),
+			JsefValue::String(string) => self.compose_string(string, false),
 			JsefValue::List(list) => self.compose_list(list, false),
 			JsefValue::Dict(dict) => self.compose_dict(dict, false),
 		}
 	}
 	
 	fn compose_list(&mut self, list: &JsefList, root: bool) -> JsefResult {
-		self.compose_many(root, '[', ']', list.iter(),
-			|this, val| this.compose_value(val)
+		if let Some(keys) = self.table_keys(list, root) {
+			return self.compose_table(list, &keys, root);
+		}
+		
+		self.compose_many(root, "[", "]", self.opts.list_separator, list.iter().enumerate(),
+			|this, (i, val)| {
+				if this.comments.is_none() {
+					return this.compose_value(val);
+				}
+				
+				let base = this.path.len();
+				this.push_path(&i.to_string());
+				this.compose_comments();
+				
+				this.compose_value(val)?;
+				this.path.truncate(base);
+				Ok(())
+			}
 		)
 	}
 	
+	/// Returns the sorted keys shared by all dicts in `list`
+	/// if it should be composed by [`Self::compose_table`].
+	fn table_keys<'l>(&self, list: &'l JsefList, root: bool) -> Option<Vec<&'l str>> {
+		if !self.opts.tabular || self.opts.indent.is_none() || self.inlines(list.len(), root) {
+			return None;
+		}
+		
+		let first = list.first()?.as_dict()?;
+		let mut keys: Vec<_> = first.keys().map(String::as_str).collect();
+		keys.sort_unstable();
+		
+		let matches = |val: &JsefValue| val.as_dict().is_some_and(|dict| {
+			dict.len() == keys.len() && keys.iter().all(|key| dict.get(*key).is_some_and(JsefValue::is_string))
+		});
+		
+		list.iter().all(matches).then_some(keys)
+	}
+	
+	/// Returns the number of chars [`Self::compose_pair`] would write for a pair with a string value.
+	fn cell_width(&self, key: &str, value: &JsefValue) -> usize {
+		let equals = if self.opts.dense {1} else {3};
+		let value = value.as_str().map_or(0, |val| self.string_width(val, false));
+		
+		self.string_width(key, true) + equals + value
+	}
+	
+	fn compose_table(&mut self, list: &JsefList, keys: &[&str], root: bool) -> JsefResult {
+		let widths: Vec<_> = keys.iter()
+			.map(|key| list.iter()
+				.filter_map(JsefValue::as_dict)
+				.map(|dict| self.cell_width(key, &dict[*key]))
+				.max()
+				.unwrap_or(0)
+			)
+			.collect();
+		
+		self.compose_many(root, "[", "]", None, list.iter(), |this, row| {
+			// table_keys only lets lists of dicts through
+			let Some(dict) = row.as_dict() else {return this.compose_value(row)};
+			
+			let inline = mem::replace(&mut this.inline, true);
+			this.compose_many(false, "{", "}", None, keys.iter().zip(&widths).enumerate(),
+				|this, (i, (key, &width))| {
+					let value = &dict[*key];
+					this.compose_pair(key, value, 0)?;
+					
+					let cell = this.cell_width(key, value);
+					if i + 1 < keys.len() && cell < width {
+						this.emit_buf(S::separator, |_, buf| {
+							buf.extend(iter::repeat_n(' ', width - cell));
+						});
+					}
+					
+					Ok(())
+				}
+			)?;
+			
+			this.inline = inline;
+			Ok(())
+		})
+	}
+	
 	fn compose_dict(&mut self, dict: &JsefDict, root: bool) -> JsefResult {
-		self.compose_many(root, '{', '}', dict.iter(),
-			|this, (key, val)| this.compose_pair(key, val)
-		)
+		// Only known once compose_many decided whether the dict is inlined
+		let mut align = None;
+		
+		let pair = |this: &mut Self, (key, val): (&String, &JsefValue)| {
+			let align = *align.get_or_insert_with(|| this.align_width(dict));
+			this.compose_pair(key, val, align)
+		};
+		
+		if self.opts.sort_keys {
+			let mut pairs: Vec<_> = dict.iter().collect();
+			pairs.sort_by_key(|(key, _)| *key);
+			self.compose_many(root, "{", "}", None, pairs.into_iter(), pair)
+		} else {
+			self.compose_many(root, "{", "}", None, dict.iter(), pair)
+		}
+	}
+}
+
+
+/// Follows the chain of single-entry dicts starting at `value`,
+/// returning the keys along the way and the value at the end of the chain.
+/// 
+/// This is the rule used by [`fold_dicts`](ComposeOpts::fold_dicts),
+/// so `{a = {b = {c = x}}}` would be folded into `a.b.c = x`.
+/// The keys are returned as-is and still have to be quoted when necessary.
+pub fn fold_path(mut value: &JsefValue) -> (Vec<&str>, &JsefValue) {
+	let mut path = Vec::new();
+	
+	while let Some(dict) = value.as_dict() {
+		if dict.len() != 1 {break;}
+		
+		// dict.len() == 1 here, so unwrap should be ok
+		let (key, val) = dict.iter().next().unwrap();
+		path.push(key.as_str());
+		value = val;
+	}
+	
+	(path, value)
+}
+
+
+/// `\u{...}` escapes for [`escape_controls`](ComposeOpts::escape_controls), indexed by codepoint.
+const CONTROL_ESCAPES: [&str; 32] = [
+	"\\u{0}",  "\\u{1}",  "\\u{2}",  "\\u{3}",  "\\u{4}",  "\\u{5}",  "\\u{6}",  "\\u{7}",
+	"\\u{8}",  "\\u{9}",  "\\u{A}",  "\\u{B}",  "\\u{C}",  "\\u{D}",  "\\u{E}",  "\\u{F}",
+	"\\u{10}", "\\u{11}", "\\u{12}", "\\u{13}", "\\u{14}", "\\u{15}", "\\u{16}", "\\u{17}",
+	"\\u{18}", "\\u{19}", "\\u{1A}", "\\u{1B}", "\\u{1C}", "\\u{1D}", "\\u{1E}", "\\u{1F}",
+];
+
+fn escape_char(c: char) -> Option<&'static str> {
+	match c {
+		'\n' => Some("\\n"),
+		'\t' => Some("\\t"),
+		'\r' => Some("\\r"),
+		'\u{c}' => Some("\\f"),
+		'\u{b}' => Some("\\v"),
+		'\0' => Some("\\0"),
+		'\\' => Some("\\\\"),
+		'"' => Some("\\\""),
+		
+		_ => None,
 	}
 }
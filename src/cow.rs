@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+
+use crash::CrashMap;
+
+use crate::{JsefValue, JsefKind};
+
+
+/// A [`JsefValue`] whose string scalars and dict keys may borrow from the parsed source.
+/// 
+/// Produced by [`parse_value_cow`](crate::parse_value_cow),
+/// strings are only allocated when escape sequences had to be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsefValueCow<'a> {
+	String(Cow<'a, str>),
+	List(Vec<JsefValueCow<'a>>),
+	Dict(CrashMap<Cow<'a, str>, JsefValueCow<'a>>),
+}
+
+/// Another name for [`JsefValueCow`], produced by [`parse_value_ref`](crate::parse_value_ref).
+pub type JsefValueRef<'a> = JsefValueCow<'a>;
+
+impl<'a> JsefValueCow<'a> {
+	pub fn kind(&self) -> JsefKind {
+		match self {
+			Self::String(_) => JsefKind::String,
+			Self::List(_) => JsefKind::List,
+			Self::Dict(_) => JsefKind::Dict,
+		}
+	}
+	
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Self::String(s) => Some(s),
+			_ => None,
+		}
+	}
+	
+	pub fn as_list(&self) -> Option<&Vec<Self>> {
+		match self {
+			Self::List(l) => Some(l),
+			_ => None,
+		}
+	}
+	
+	pub fn as_dict(&self) -> Option<&CrashMap<Cow<'a, str>, Self>> {
+		match self {
+			Self::Dict(d) => Some(d),
+			_ => None,
+		}
+	}
+	
+	pub fn as_dict_mut(&mut self) -> Option<&mut CrashMap<Cow<'a, str>, Self>> {
+		match self {
+			Self::Dict(d) => Some(d),
+			_ => None,
+		}
+	}
+	
+	/// Converts the value into a [`JsefValue`], allocating all borrowed strings.
+	pub fn into_owned(self) -> JsefValue {
+		match self {
+			Self::String(s) => JsefValue::String(s.into_owned()),
+			Self::List(l) => JsefValue::List(l.into_iter().map(Self::into_owned).collect()),
+			
+			Self::Dict(d) => JsefValue::Dict(d
+				.into_iter()
+				.map(|(key, val)| (key.into_owned(), val.into_owned()))
+				.collect()
+			),
+		}
+	}
+	
+	/// Copies the value into a [`JsefValue`], leaving this one as it is.
+	/// 
+	/// Shadows [`ToOwned::to_owned`], use [`Clone::clone`] for a copy of the borrowing value.
+	pub fn to_owned(&self) -> JsefValue {
+		match self {
+			Self::String(s) => JsefValue::String(s.as_ref().to_owned()),
+			Self::List(l) => JsefValue::List(l.iter().map(Self::to_owned).collect()),
+			
+			Self::Dict(d) => JsefValue::Dict(d
+				.iter()
+				.map(|(key, val)| (key.as_ref().to_owned(), val.to_owned()))
+				.collect()
+			),
+		}
+	}
+}
@@ -0,0 +1,66 @@
+use crate::{JsefValue, join_path};
+
+
+/// A difference between two values found by [`JsefValue::diff`].
+/// 
+/// Paths are dotted, with list items addressed by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+	/// Only the new value has something at the path.
+	Added(String, &'a JsefValue),
+	/// Only the old value has something at the path.
+	Removed(String, &'a JsefValue),
+	/// Both values have something different at the path, holding the old and the new one.
+	Modified(String, &'a JsefValue, &'a JsefValue),
+}
+
+impl Change<'_> {
+	/// Returns the dotted path the change is at.
+	pub fn path(&self) -> &str {
+		match self {
+			Self::Added(path, _) | Self::Removed(path, _) | Self::Modified(path, _, _) => path,
+		}
+	}
+}
+
+
+/// See [`JsefValue::diff`].
+pub(crate) fn diff<'a>(old: &'a JsefValue, new: &'a JsefValue) -> Vec<Change<'a>> {
+	let mut changes = Vec::new();
+	let mut stack = vec![(String::new(), old, new)];
+	
+	while let Some((path, old, new)) = stack.pop() {
+		match (old, new) {
+			(JsefValue::Dict(old), JsefValue::Dict(new)) => {
+				for (key, old) in old {
+					let path = join_path(&path, key);
+					
+					match new.get(key) {
+						Some(new) => stack.push((path, old, new)),
+						None => changes.push(Change::Removed(path, old)),
+					}
+				}
+				
+				changes.extend(new
+					.iter()
+					.filter(|(key, _)| !old.contains_key(*key))
+					.map(|(key, new)| Change::Added(join_path(&path, key), new))
+				);
+			},
+			
+			(JsefValue::List(old), JsefValue::List(new)) => {
+				let path = |i: usize| join_path(&path, &i.to_string());
+				
+				stack.extend(old.iter().zip(new).enumerate().rev().map(|(i, (old, new))| (path(i), old, new)));
+				changes.extend(old.iter().enumerate().skip(new.len()).map(|(i, old)| Change::Removed(path(i), old)));
+				changes.extend(new.iter().enumerate().skip(old.len()).map(|(i, new)| Change::Added(path(i), new)));
+			},
+			
+			(old, new) => if old != new {
+				changes.push(Change::Modified(path, old, new));
+			},
+		}
+	}
+	
+	changes
+}
@@ -1,4 +1,6 @@
-use std::{fmt, error};
+use std::{fmt, error, io, iter};
+
+use crate::JsefKind;
 
 
 pub type JsefResult<T = ()> = Result<T, JsefErr>;
@@ -9,17 +11,98 @@ pub struct JsefErr {
 	pub err: JsefErrType,
 	pub line: usize,
 	pub col: usize,
+	/// The byte offset that `line` and `col` point at.
+	pub byte: usize,
+	/// The key of the dict pair that was being parsed, if it had already been read.
+	pub key: Option<String>,
+	/// The included file the error occurred in, as named by its directive.
+	/// `None` for the file passed to [`load_with_includes`](crate::load_with_includes) and for everything else.
+	pub file: Option<String>,
 }
 
 impl JsefErr {
-	pub const fn new(err: JsefErrType, line: usize, col: usize) -> Self {
-		Self {err, line, col}
+	pub const fn new(err: JsefErrType, line: usize, col: usize, byte: usize) -> Self {
+		Self {err, line, col, byte, key: None, file: None}
+	}
+	
+	/// Sets [`key`](Self::key) unless the error already occurred within a more deeply nested pair.
+	pub fn in_key(mut self, key: &str) -> Self {
+		if self.key.is_none() {
+			self.key = Some(key.to_owned());
+		}
+		
+		self
+	}
+	
+	/// Sets [`file`](Self::file) unless the error already occurred within a more deeply included file.
+	pub fn in_file(mut self, file: &str) -> Self {
+		if self.file.is_none() {
+			self.file = Some(file.to_owned());
+		}
+		
+		self
+	}
+	
+	/// Flattens the error into a [`JsefDiagnostic`], whose shape stays stable for tools to rely on.
+	pub fn to_diagnostic(&self) -> JsefDiagnostic {
+		JsefDiagnostic {
+			message: self.message(),
+			line: self.line,
+			col: self.col,
+			offset: self.byte,
+			file: self.file.clone(),
+			code: self.err.code(),
+		}
+	}
+	
+	/// Formats the error followed by the offending line of `source` and a caret under the column.
+	/// 
+	/// Positions past the end of `source`, as reported for EOF errors, point after its last line.
+	/// Errors without a position, such as those from value methods like [`try_path_entry`](crate::JsefValue::try_path_entry),
+	/// are rendered like [`Display`](fmt::Display).
+	pub fn render_with_source(&self, source: &str) -> String {
+		if self.line == 0 {
+			return self.to_string();
+		}
+		
+		let lines: Vec<&str> = source.lines().collect();
+		let (line, col) = match lines.get(self.line - 1) {
+			Some(_) => (self.line, self.col.max(1)),
+			None => {
+				let last = lines.last().copied().unwrap_or_default();
+				(lines.len().max(1), last.chars().count() + 1)
+			},
+		};
+		
+		let text = lines.get(line - 1).copied().unwrap_or_default();
+		let text = text.strip_suffix('\r').unwrap_or(text);
+		
+		// Keep tabs so the caret lines up with the text above it
+		let pad: String = text.chars()
+			.chain(iter::repeat(' '))
+			.take(col - 1)
+			.map(|c| if c == '\t' {'\t'} else {' '})
+			.collect();
+		
+		let gutter = " ".repeat(line.to_string().len());
+		format!("{self}\n{line} | {text}\n{gutter} | {pad}^")
+	}
+	
+	fn message(&self) -> String {
+		match &self.key {
+			Some(key) => format!("while parsing key \"{key}\": {}", self.err),
+			None => self.err.to_string(),
+		}
 	}
 }
 
 impl fmt::Display for JsefErr {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		write!(f, "JSeF error at line {}, col {}: {}", self.line, self.col, self.err)
+		if let Some(file) = &self.file {
+			write!(f, "JSeF error in \"{file}\" at line {}, col {}: {}", self.line, self.col, self.message())
+		} else {
+			write!(f, "JSeF error at line {}, col {}: {}", self.line, self.col, self.message())
+		}
 	}
 }
 
@@ -33,6 +116,61 @@ pub enum JsefErrType {
 	Mismatch(char, Option<char>),
 	NotEof(char),
 	MaxDepth,
+	Io(io::ErrorKind),
+	IncludeCycle(String),
+	/// A key exceeded [`max_key_len`](crate::ParseOpts::max_key_len),
+	/// holding the first [`KEY_PREFIX_LEN`](crate::KEY_PREFIX_LEN) chars of it.
+	KeyTooLong(String),
+	/// An unescaped control char rejected by [`raw_controls`](crate::ParseOpts::raw_controls).
+	RawControl(char),
+	/// A path was assigned a value of a different kind than it already had,
+	/// reported with [`strict_types`](crate::ParseOpts::strict_types)
+	/// or when [`merge_str`](crate::JsefValue::merge_str) is called on a non-dict.
+	TypeConflict {
+		/// The path as written in the offending pair, up to the conflicting key.
+		path: String,
+		expected: JsefKind,
+		found: JsefKind,
+	},
+	/// Composing would have written more than [`max_output_bytes`](crate::ComposeOpts::max_output_bytes),
+	/// holding the limit.
+	OutputTooLong(usize),
+	/// A `\u{...}` escape for a surrogate or a codepoint above `10FFFF`.
+	InvalidCodepoint(u32),
+	/// A key was assigned to twice within the same dict,
+	/// reported with [`reject_duplicates`](crate::ParseOpts::reject_duplicates).
+	DuplicateKey(String),
+	/// An unknown escape sequence, reported with [`strict_escapes`](crate::ParseOpts::strict_escapes).
+	InvalidEscape(char),
+	/// A quoted string ending in a backslash, which can't be composed with
+	/// [`raw_strings`](crate::ComposeOpts::raw_strings) since it would escape the closing quote.
+	TrailingBackslash,
+	/// A [`list_separator`](crate::ComposeOpts::list_separator) that's empty or has chars
+	/// other than ASCII whitespace and commas, holding the separator, since the output wouldn't parse back.
+	InvalidSeparator(String),
+}
+
+impl JsefErrType {
+	/// Returns a stable identifier of the error kind that tools can match on.
+	pub const fn code(&self) -> &'static str {
+		match self {
+			Self::Unexpected(_)  => "unexpected",
+			Self::Mismatch(_, _) => "mismatch",
+			Self::NotEof(_)      => "not_eof",
+			Self::MaxDepth       => "max_depth",
+			Self::Io(_)          => "io",
+			Self::IncludeCycle(_) => "include_cycle",
+			Self::KeyTooLong(_)  => "key_too_long",
+			Self::RawControl(_)  => "raw_control",
+			Self::TypeConflict {..} => "type_conflict",
+			Self::OutputTooLong(_) => "output_too_long",
+			Self::InvalidCodepoint(_) => "invalid_codepoint",
+			Self::DuplicateKey(_)  => "duplicate_key",
+			Self::InvalidEscape(_) => "invalid_escape",
+			Self::TrailingBackslash => "trailing_backslash",
+			Self::InvalidSeparator(_) => "invalid_separator",
+		}
+	}
 }
 
 impl fmt::Display for JsefErrType {
@@ -44,8 +182,40 @@ impl fmt::Display for JsefErrType {
 			Self::Mismatch(e, Some(g)) => write!(f, "expected '{e}', got '{g}'"),
 			Self::NotEof(c)            => write!(f, "expected EOF, got '{c}'"),
 			Self::MaxDepth             => write!(f, "maximum nesting depth exceeded"),
+			Self::Io(kind)             => write!(f, "IO error: {kind}"),
+			Self::IncludeCycle(name)   => write!(f, "\"{name}\" is included in a cycle"),
+			Self::KeyTooLong(prefix)   => write!(f, "key starting with \"{prefix}\" is too long"),
+			Self::RawControl(c)        => write!(f, "unescaped control char U+{:04X} in string", *c as u32),
+			Self::TypeConflict {path, expected, found} =>
+				write!(f, "expected {expected} at \"{path}\", got {found}"),
+			Self::OutputTooLong(max)   => write!(f, "composed output exceeds {max} bytes"),
+			Self::InvalidCodepoint(c)  => write!(f, "U+{c:04X} is not a valid char"),
+			Self::DuplicateKey(key)    => write!(f, "duplicate key \"{key}\""),
+			Self::InvalidEscape(c)     => write!(f, "unknown escape sequence '\\{c}'"),
+			Self::TrailingBackslash    => write!(f, "raw string ends in a backslash"),
+			Self::InvalidSeparator(sep) => write!(f, "list separator \"{sep}\" isn't made of whitespace and commas"),
 		}
 	}
 }
 
 impl error::Error for JsefErrType {}
+
+
+/// A flattened, machine-readable form of a [`JsefErr`] for language servers and CI annotations.
+/// 
+/// Implements `Serialize` with the `serde` feature, as a map with the field names as keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsefDiagnostic {
+	/// The human-readable description of the error, without the position.
+	pub message: String,
+	/// The 1-based line of the error, or 0 if it has no position.
+	pub line: usize,
+	/// The 1-based column in chars within `line`, or 0 if the error has no position.
+	pub col: usize,
+	/// The byte offset into the source that `line` and `col` point at, the same as [`JsefErr::byte`].
+	pub offset: usize,
+	/// See [`JsefErr::file`].
+	pub file: Option<String>,
+	/// See [`JsefErrType::code`].
+	pub code: &'static str,
+}
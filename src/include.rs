@@ -0,0 +1,85 @@
+use std::io::{self, Read};
+
+use crate::{
+	JsefDict,
+	JsefErrType::{self, *},
+	JsefErr, JsefResult,
+	ParseOpts, DEPTH_LIMIT,
+	parse::Parser,
+	count_line_col, merge_dicts,
+};
+
+
+/// Loads a [`JsefDict`] from `path`, expanding `#include "other"` comment directives.
+/// 
+/// `resolver` maps `path` and the names of included files to readers of their contents.
+/// Every included file is parsed as a root dict and deep-merged in the order of the directives,
+/// after which the entries of the including file itself are merged on top.
+/// 
+/// Directives are only recognized in comments on their own lines at the root level,
+/// since they always merge at the root. Within lists and dicts or after a pair, they stay plain comments.
+/// 
+/// Includes can be nested up to [`DEPTH_LIMIT`] levels and fail with
+/// [`IncludeCycle`](JsefErrType::IncludeCycle) if a file ends up including itself.
+/// Resolver errors point at the offending directive, or at line and col 0 for `path` itself.
+/// Parse errors always point into the file they occurred in,
+/// and errors within an included file name it in [`JsefErr::file`].
+pub fn load_with_includes<F, R>(path: &str, mut resolver: F) -> JsefResult<JsefDict>
+where
+	F: FnMut(&str) -> io::Result<R>,
+	R: Read,
+{
+	let source = read(&mut resolver, path)
+		.map_err(|e| JsefErr::new(Io(e.kind()), 0, 0, 0))?;
+	
+	let mut chain = vec![path.to_owned()];
+	load(&source, &mut resolver, &mut chain)
+}
+
+
+fn read<F, R>(resolver: &mut F, path: &str) -> io::Result<String>
+where
+	F: FnMut(&str) -> io::Result<R>,
+	R: Read,
+{
+	let mut source = String::new();
+	resolver(path)?.read_to_string(&mut source)?;
+	Ok(source)
+}
+
+fn load<F, R>(source: &str, resolver: &mut F, chain: &mut Vec<String>) -> JsefResult<JsefDict>
+where
+	F: FnMut(&str) -> io::Result<R>,
+	R: Read,
+{
+	let (own, includes) = Parser::new(source, &ParseOpts::DEFAULT).parse_dict_root_includes()?;
+	let mut dict = JsefDict::default();
+	
+	for (name, idx) in includes {
+		let err = |err: JsefErrType| {
+			let (line, col) = count_line_col(&source[..idx]);
+			JsefErr::new(err, line, col, idx)
+		};
+		
+		if chain.contains(&name) {
+			return Err(err(IncludeCycle(name)));
+		}
+		
+		if chain.len() > DEPTH_LIMIT {
+			return Err(err(MaxDepth));
+		}
+		
+		let included = read(resolver, &name)
+			.map_err(|e| err(Io(e.kind())))?;
+		
+		chain.push(name);
+		let included = load(&included, resolver, chain)
+			.map_err(|e| e.in_file(chain.last().unwrap()))?;
+		chain.pop();
+		
+		merge_dicts(&mut dict, included);
+	}
+	
+	merge_dicts(&mut dict, own);
+	Ok(dict)
+}
\ No newline at end of file
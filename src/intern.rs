@@ -0,0 +1,80 @@
+#![cfg(feature = "intern")]
+
+
+use std::{
+	collections::HashSet,
+	sync::Arc,
+};
+
+use crash::CrashMap;
+
+use crate::JsefValue;
+
+
+/// A [`JsefValue`] where all equal keys and strings share a single allocation.
+/// 
+/// Created by [`intern_values`], useful for large generated configs that repeat the same values a lot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedValue {
+	String(Arc<str>),
+	List(Vec<SharedValue>),
+	Dict(CrashMap<Arc<str>, SharedValue>),
+}
+
+impl SharedValue {
+	/// Converts the value back into a [`JsefValue`] with plain, unshared strings.
+	pub fn to_value(&self) -> JsefValue {
+		match self {
+			Self::String(s) => JsefValue::String(s.to_string()),
+			Self::List(l) => JsefValue::List(l.iter().map(Self::to_value).collect()),
+			
+			Self::Dict(d) => JsefValue::Dict(d
+				.iter()
+				.map(|(key, val)| (key.to_string(), val.to_value()))
+				.collect()
+			),
+		}
+	}
+}
+
+
+/// Converts `value` into a [`SharedValue`], deduplicating all equal keys and strings.
+pub fn intern_values(value: JsefValue) -> SharedValue {
+	Interner::default().intern_value(value)
+}
+
+
+#[derive(Debug, Default)]
+struct Interner {
+	strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+	fn intern(&mut self, string: String) -> Arc<str> {
+		if let Some(shared) = self.strings.get(string.as_str()) {
+			return Arc::clone(shared);
+		}
+		
+		let shared: Arc<str> = string.into();
+		self.strings.insert(Arc::clone(&shared));
+		shared
+	}
+	
+	fn intern_value(&mut self, value: JsefValue) -> SharedValue {
+		match value {
+			JsefValue::String(s) => SharedValue::String(self.intern(s)),
+			
+			JsefValue::List(l) => SharedValue::List(l
+				.into_iter()
+				.map(|val| self.intern_value(val))
+				.collect()
+			),
+			
+			JsefValue::Dict(d) => SharedValue::Dict(d
+				.into_iter()
+				.map(|(key, val)| (self.intern(key), self.intern_value(val)))
+				.collect()
+			),
+		}
+	}
+}
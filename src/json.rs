@@ -0,0 +1,108 @@
+#![cfg(feature = "json")]
+
+use std::borrow::Cow;
+
+use serde_json::{Value, Map};
+
+use crate::{
+	JsefValue, JsefKind, JsefResult,
+	ParseOpts, float_string,
+	parse::{Parser, Node},
+};
+
+
+/// Parses the input string straight into a [`serde_json::Value`],
+/// without building a [`JsefValue`](crate::JsefValue) first.
+/// 
+/// Requires root lists and dicts to be enclosed in the appropriate brackets.
+/// Scalars always become JSON strings.
+pub fn parse_to_json<S>(source: &S) -> JsefResult<Value>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), &ParseOpts::DEFAULT).parse_value_root()
+}
+
+
+/// Numbers and bools become strings of their JSON text, so `1` and `"1"` end up the same.
+/// Floats keep their fraction and `null` becomes an empty string, the same as deserializing with serde.
+impl From<Value> for JsefValue {
+	fn from(value: Value) -> Self {
+		match value {
+			Value::Null => Self::String(String::new()),
+			Value::Bool(b) => Self::String(b.to_string()),
+			Value::Number(n) => match n.as_f64() {
+				Some(float) if n.is_f64() => Self::String(float_string(float)),
+				_ => Self::String(n.to_string()),
+			},
+			Value::String(s) => Self::String(s),
+			Value::Array(a) => Self::List(a.into_iter().map(Self::from).collect()),
+			
+			Value::Object(o) => Self::Dict(o
+				.into_iter()
+				.map(|(key, val)| (key, Self::from(val)))
+				.collect()
+			),
+		}
+	}
+}
+
+/// Scalars always become JSON strings, since there's no telling which ones were meant as numbers.
+impl From<JsefValue> for Value {
+	fn from(value: JsefValue) -> Self {
+		match value {
+			JsefValue::String(s) => Self::String(s),
+			JsefValue::List(l) => Self::Array(l.into_iter().map(Self::from).collect()),
+			
+			JsefValue::Dict(d) => Self::Object(d
+				.into_iter()
+				.map(|(key, val)| (key, Self::from(val)))
+				.collect()
+			),
+		}
+	}
+}
+
+
+impl<'s> Node<'s> for Value {
+	type Map = Map<String, Value>;
+	
+	fn from_string(string: Cow<'s, str>) -> Self {
+		Self::String(string.into_owned())
+	}
+	
+	fn from_list(list: Vec<Self>) -> Self {
+		Self::Array(list)
+	}
+	
+	fn from_dict(dict: Self::Map) -> Self {
+		Self::Object(dict)
+	}
+	
+	fn kind(&self) -> JsefKind {
+		match self {
+			Self::Array(_) => JsefKind::List,
+			Self::Object(_) => JsefKind::Dict,
+			_ => JsefKind::String,
+		}
+	}
+	
+	fn kind_at(dict: &Self::Map, key: &str) -> Option<JsefKind> {
+		dict.get(key).map(Node::kind)
+	}
+	
+	fn path_dict<'d>(dict: &'d mut Self::Map, key: Cow<'s, str>) -> &'d mut Self::Map {
+		let value = dict
+			.entry(key.into_owned())
+			.or_insert_with(|| Self::Object(Map::new()));
+		
+		if !value.is_object() {
+			*value = Self::Object(Map::new());
+		}
+		
+		// unwrap should be safe, value was just made sure to be a Value::Object
+		value.as_object_mut().unwrap()
+	}
+	
+	fn insert(dict: &mut Self::Map, key: Cow<'s, str>, value: Self) {
+		dict.insert(key.into_owned(), value);
+	}
+}
@@ -0,0 +1,457 @@
+use std::{array, borrow::Cow, ops::Range};
+
+use crate::{
+	JsefErrType::{self, *},
+	JsefErr, JsefResult,
+	ParseOpts,
+	count_line_col,
+};
+
+
+/// A single token of JSeF source, along with the span it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'s> {
+	pub kind: TokenKind<'s>,
+	pub line: usize,
+	pub col: usize,
+	/// The position right after the token.
+	pub end_line: usize,
+	pub end_col: usize,
+	/// The byte offsets of the token within the source, including quotes and the comment lead.
+	pub bytes: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind<'s> {
+	OpenBrace,
+	CloseBrace,
+	OpenBracket,
+	CloseBracket,
+	Equals,
+	Dot,
+	/// An unquoted word.
+	Word(Cow<'s, str>),
+	/// The contents of a quoted string with escape sequences decoded.
+	QuotedString(Cow<'s, str>),
+	/// The text of a line comment after the [`comment_lead`](ParseOpts::comment_lead).
+	Comment(&'s str),
+}
+
+
+/// Splits JSeF source into [`Token`]s without building any values.
+/// 
+/// Whitespace is skipped, and so are commas with [`commas_ok`](ParseOpts::commas_ok).
+/// Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Lexer<'s, 'o> {
+	opts: &'o ParseOpts,
+	pub(crate) source: &'s str,
+	peek: Option<char>,
+	pub(crate) idx: usize,
+	/// `#include "..."` directives along with their byte offsets, only collected when set
+	/// and only from comments on their own lines outside of [`nested`](Self::nested) collections.
+	pub(crate) includes: Option<Vec<(String, usize)>>,
+	/// Whether the parser is within a bracketed list or dict, kept up to date for collecting includes.
+	pub(crate) nested: bool,
+	/// Texts of the comments on their own lines since the parser last took them, only collected when set.
+	pub(crate) comments: Option<Vec<&'s str>>,
+	/// A position whose line and col are already known, so tokens don't have to recount from the start.
+	mark: (usize, usize, usize),
+	/// [`ParseOpts::is_word_char`] for every ASCII char, so words can be scanned byte by byte.
+	word_bytes: [bool; 128],
+	failed: bool,
+}
+
+impl<'s, 'o> Lexer<'s, 'o> {
+	pub fn new(source: &'s str, opts: &'o ParseOpts) -> Self {
+		let idx = skip_lines(source, opts.skip_lines);
+		let peek = source[idx..].chars().next();
+		let (line, col) = count_line_col(&source[..idx]);
+		let word_bytes = array::from_fn(|b| opts.is_word_char(b as u8 as char));
+		
+		Self {
+			includes: None,
+			nested: false,
+			comments: None,
+			mark: (idx, line, col),
+			word_bytes,
+			failed: false,
+			source, peek, idx, opts,
+		}
+	}
+	
+	/// Returns the next token, or `None` at the end of the source.
+	pub fn next_token(&mut self) -> JsefResult<Option<Token<'s>>> {
+		loop {
+			self.next_while(|c| c.is_ascii_whitespace());
+			if !(self.opts.commas_ok && self.try_eat(',')) {break;}
+		}
+		
+		let start = self.idx;
+		let (line, col) = self.position();
+		let Some(c) = self.peek() else {return Ok(None)};
+		
+		let kind = if self.at_comment() {
+			self.eat_lead();
+			TokenKind::Comment(self.next_while(|c| c != '\n'))
+		} else {
+			self.next_token_kind(c)?
+		};
+		
+		let (end_line, end_col) = self.position();
+		Ok(Some(Token {kind, line, col, end_line, end_col, bytes: start..self.idx}))
+	}
+	
+	fn next_token_kind(&mut self, c: char) -> JsefResult<TokenKind<'s>> {
+		let kind = match c {
+			'{' | '}' | '[' | ']' | '=' | '.' => {
+				self.bump();
+				
+				match c {
+					'{' => TokenKind::OpenBrace,
+					'}' => TokenKind::CloseBrace,
+					'[' => TokenKind::OpenBracket,
+					']' => TokenKind::CloseBracket,
+					'=' => TokenKind::Equals,
+					_ => TokenKind::Dot,
+				}
+			},
+			
+			'"' => TokenKind::QuotedString(self.parse_string()?),
+			_ => TokenKind::Word(self.parse_word()?),
+		};
+		
+		Ok(kind)
+	}
+	
+	/// Returns the line and col at the current index, counting from the last known position.
+	fn position(&mut self) -> (usize, usize) {
+		let (idx, line, col) = self.mark;
+		let (lines, cols) = count_line_col(&self.source[idx..self.idx]);
+		
+		let pos = if lines > 1 {
+			(line + lines - 1, cols)
+		} else {
+			(line, col + cols - 1)
+		};
+		
+		self.mark = (self.idx, pos.0, pos.1);
+		pos
+	}
+}
+
+impl<'s> Iterator for Lexer<'s, '_> {
+	type Item = JsefResult<Token<'s>>;
+	
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.failed {
+			return None;
+		}
+		
+		let token = self.next_token();
+		self.failed = token.is_err();
+		token.transpose()
+	}
+}
+
+impl<'s> Lexer<'s, '_> {
+	pub(crate) fn err(&self, err: JsefErrType) -> JsefErr {
+		self.err_at(err, self.idx)
+	}
+	
+	pub(crate) fn err_at(&self, err: JsefErrType, idx: usize) -> JsefErr {
+		let (line, col) = count_line_col(&self.source[..idx]);
+		JsefErr::new(err, line, col, idx)
+	}
+	
+	fn slice(&self) -> &str {
+		&self.source[self.idx..]
+	}
+	
+	pub(crate) fn peek(&self) -> Option<char> {
+		self.peek
+	}
+	
+	fn bump(&mut self) -> Option<char> {
+		let prev = self.peek;
+		// The peeked char is already decoded, so there's no need to search for the next boundary
+		self.idx += prev.map_or(0, char::len_utf8);
+		self.peek = self.slice().chars().next();
+		
+		prev
+	}
+	
+	fn take_char(&mut self) -> JsefResult<char> {
+		self.bump().ok_or_else(|| self.err(Unexpected(None)))
+	}
+	
+	fn next_while<F>(&mut self, mut pred: F) -> &'s str
+	where F: FnMut(char) -> bool {
+		let slice = self.slice();
+		let len = slice.len();
+		let start = self.idx;
+		
+		let find = slice
+			.char_indices()
+			.find(|(_, c)| !pred(*c));
+		
+		if let Some((i, c)) = find {
+			self.peek = Some(c);
+			self.idx += i;
+		} else {
+			self.peek = None;
+			self.idx += len;
+		}
+		
+		let source = self.source;
+		&source[start..self.idx]
+	}
+	
+	/// Like [`Self::next_while`] with [`ParseOpts::is_word_char`],
+	/// but only decodes chars that aren't ASCII, since words are usually made of nothing else.
+	fn next_word(&mut self) -> &'s str {
+		let source = self.source;
+		let bytes = source.as_bytes();
+		let start = self.idx;
+		let mut idx = start;
+		
+		while let Some(&b) = bytes.get(idx) {
+			if b.is_ascii() {
+				if !self.word_bytes[b as usize] {break;}
+				idx += 1;
+			} else {
+				// idx is always on a char boundary, so there is a char here
+				let c = source[idx..].chars().next().unwrap();
+				if !self.opts.is_word_char(c) {break;}
+				idx += c.len_utf8();
+			}
+		}
+		
+		self.idx = idx;
+		self.peek = source[idx..].chars().next();
+		&source[start..idx]
+	}
+	
+	/// Like [`Self::next_while`] with the chars quoted strings may contain unescaped, but scans bytes.
+	/// Quotes, backslashes and rejected controls are all ASCII,
+	/// which never shows up within the encoding of any other char, so nothing needs to be decoded.
+	fn next_plain(&mut self) -> &'s str {
+		let source = self.source;
+		let bytes = source.as_bytes();
+		let start = self.idx;
+		
+		let len = bytes[start..]
+			.iter()
+			.position(|&b| b == b'"' || b == b'\\' || (b.is_ascii() && self.opts.rejects_raw(b as char)))
+			.unwrap_or(bytes.len() - start);
+		
+		self.idx = start + len;
+		self.peek = source[self.idx..].chars().next();
+		&source[start..self.idx]
+	}
+	
+	pub(crate) fn eat(&mut self, c: char) -> JsefResult {
+		// Can't call Self::next right away,
+		// since that would screw up the error line-column reporting
+		match self.peek() {
+			Some(p) if p == c => {
+				self.bump();
+				Ok(())
+			},
+			
+			p => Err(self.err(Mismatch(c, p))),
+		}
+	}
+	
+	pub(crate) fn try_eat(&mut self, c: char) -> bool {
+		if self.peek() == Some(c) {
+			self.bump();
+			true
+		} else {
+			false
+		}
+	}
+	
+	pub(crate) fn assert_eof(&self) -> JsefResult {
+		match self.peek() {
+			Some(p) => Err(self.err(NotEof(p))),
+			None => Ok(()),
+		}
+	}
+	
+	/// Returns whether a comment starts at the current index.
+	fn at_comment(&self) -> bool {
+		let lead = self.opts.comment_lead;
+		!lead.is_empty() && self.slice().starts_with(lead)
+	}
+	
+	/// Skips the [`comment_lead`](ParseOpts::comment_lead) at the current index.
+	fn eat_lead(&mut self) {
+		for _ in self.opts.comment_lead.chars() {
+			self.bump();
+		}
+	}
+	
+	pub(crate) fn skip_whitespace(&mut self) {
+		while let Some(c) = self.peek() {
+			if c.is_ascii_whitespace() {
+				self.next_while(|c| c.is_ascii_whitespace());
+				continue;
+			}
+			
+			if self.at_comment() {
+				let idx = self.idx;
+				self.eat_lead();
+				let comment = self.next_while(|c| c != '\n');
+				
+				// Comments that follow something on the same line are dropped
+				let line = self.source[..idx].rsplit('\n').next().unwrap_or_default();
+				let own_line = line.trim_ascii().is_empty();
+				
+				if let Some(includes) = &mut self.includes
+				&& own_line && !self.nested
+				&& let Some(name) = parse_include(comment) {
+					includes.push((name.to_owned(), idx));
+				}
+				
+				if let Some(comments) = &mut self.comments
+				&& own_line {
+					comments.push(comment);
+				}
+				
+				continue;
+			}
+			
+			break;
+		}
+	}
+	
+	pub(crate) fn skip_separators(&mut self) {
+		self.skip_whitespace();
+		
+		while self.opts.commas_ok && self.try_eat(',') {
+			self.skip_whitespace();
+		}
+	}
+	
+	pub(crate) fn parse_word(&mut self) -> JsefResult<Cow<'s, str>> {
+		let slice = self.next_word();
+		
+		if !slice.is_empty() {
+			Ok(Cow::Borrowed(slice))
+		} else {
+			Err(self.err(Unexpected(self.peek())))
+		}
+	}
+	
+	fn parse_escape(&mut self) -> JsefResult<char> {
+		let start = self.idx;
+		self.eat('\\')?;
+		
+		match self.take_char()? {
+			'n' => Ok('\n'),
+			't' => Ok('\t'),
+			'r' => Ok('\r'),
+			'f' => Ok('\u{c}'),
+			'v' => Ok('\u{b}'),
+			'0' => Ok('\0'),
+			'u' => self.parse_unicode_escape(start),
+			c @ ('\\' | '"') => Ok(c),
+			
+			c if self.opts.strict_escapes => Err(self.err_at(InvalidEscape(c), start)),
+			c => Ok(c),
+		}
+	}
+	
+	/// Parses the `{1F600}` part of a `\u{1F600}` escape that starts at `start`.
+	fn parse_unicode_escape(&mut self, start: usize) -> JsefResult<char> {
+		if !self.try_eat('{') {
+			return Err(self.err(Unexpected(self.peek())));
+		}
+		
+		let mut count = 0;
+		let digits = self.next_while(|c| {
+			count += 1;
+			count <= 6 && c.is_ascii_hexdigit()
+		});
+		
+		if digits.is_empty() || !self.try_eat('}') {
+			return Err(self.err(Unexpected(self.peek())));
+		}
+		
+		// At most 6 hex digits always fit into a u32
+		let code = u32::from_str_radix(digits, 16).unwrap();
+		char::from_u32(code).ok_or_else(|| self.err_at(InvalidCodepoint(code), start))
+	}
+	
+	fn parse_raw_escape(&mut self) -> JsefResult<char> {
+		self.eat('\\')?;
+		
+		if self.try_eat('"') {
+			Ok('"')
+		} else {
+			Ok('\\')
+		}
+	}
+	
+	pub(crate) fn parse_string(&mut self) -> JsefResult<Cow<'s, str>> {
+		self.eat('"')?;
+		
+		let opts = self.opts;
+		
+		// Only allocate once an escape sequence shows up
+		let slice = self.next_plain();
+		let mut string = Cow::Borrowed(slice);
+		
+		loop {
+			match self.peek() {
+				Some('\\') => {
+					let c = if opts.raw_strings {
+						self.parse_raw_escape()?
+					} else {
+						self.parse_escape()?
+					};
+					
+					let string = string.to_mut();
+					string.push(c);
+					
+					let slice = self.next_plain();
+					string.push_str(slice);
+				},
+				
+				Some(c) if opts.rejects_raw(c) => return Err(self.err(RawControl(c))),
+				_ => break,
+			}
+		}
+		
+		self.eat('"')?;
+		Ok(string)
+	}
+	
+	pub(crate) fn parse_ident(&mut self) -> JsefResult<Cow<'s, str>> {
+		match self.peek() {
+			Some('"') => self.parse_string(),
+			_ => self.parse_word(),
+		}
+	}
+	
+}
+
+
+/// Extracts the name from the text of an `#include "name"` comment after its lead.
+fn parse_include(comment: &str) -> Option<&str> {
+	comment
+		.strip_prefix("include")?
+		.trim()
+		.strip_prefix('"')?
+		.strip_suffix('"')
+}
+
+
+/// Returns the byte offset right after the first `count` lines of `source`.
+fn skip_lines(source: &str, count: usize) -> usize {
+	match count.checked_sub(1) {
+		Some(n) => source.match_indices('\n').nth(n).map_or(source.len(), |(i, _)| i + 1),
+		None => 0,
+	}
+}
@@ -4,55 +4,222 @@
 mod test;
 mod err;
 mod value;
+mod cow;
+mod lex;
 mod parse;
+mod stream;
 mod compose;
+mod lint;
+mod diff;
+mod normalize;
+mod intern;
+mod include;
+mod assert;
+mod macros;
+mod json;
+mod serde_impl;
 
 pub use err::*;
 pub use value::*;
-pub use compose::ComposeOpts;
+pub use cow::{JsefValueCow, JsefValueRef};
+pub use lex::{Lexer, Token, TokenKind};
+pub use parse::{ParseOpts, ParseInfo, ParseEnd, RawControls, Event};
+pub use stream::{
+	parse_value_from, parse_list_from, parse_dict_from,
+	parse_value_from_bytes, parse_list_from_bytes, parse_dict_from_bytes,
+	parse_events,
+};
+pub use compose::{ComposeOpts, ComposeOptsOwned, ComposeSink, UnknownPreset, fold_path};
+pub use lint::{SuspiciousScalar, lint_scalars};
+pub use diff::Change;
+pub use normalize::canonicalize_bools;
+pub use include::load_with_includes;
+#[doc(hidden)]
+pub use assert::assert_failed as __assert_jsef_failed;
+#[doc(hidden)]
+pub use macros::scalar as __jsef_scalar;
+#[cfg(feature = "intern")]
+pub use intern::{SharedValue, intern_values};
+#[cfg(feature = "json")]
+pub use json::parse_to_json;
+
+use std::{iter, io::{BufRead, Write}, collections::HashMap};
 
 use crash::CrashMap;
 use parse::Parser;
-use compose::Composer;
+use compose::{Composer, LenCounter, WriteSink};
 
 
-/// Maximum nesting level for parsing and composing.
+/// Maximum nesting level for composing, and for parsing unless [`ParseOpts::max_depth`] is changed.
 #[cfg(not(test))]
 pub const DEPTH_LIMIT: usize = 256;
 
 #[cfg(test)]
 pub const DEPTH_LIMIT: usize = 2;
 
+/// Number of chars of an overly long key kept in [`KeyTooLong`](JsefErrType::KeyTooLong) errors.
+pub const KEY_PREFIX_LEN: usize = 16;
+
 
 /// A list of [`JsefValue`]s.
 pub type JsefList = Vec<JsefValue>;
 
 /// A string-keyed map of [`JsefValue`]s.
+/// 
+/// Keys are always plain strings, even numeric-looking ones like `0`,
+/// and stay that way regardless of how values end up being interpreted.
 pub type JsefDict = CrashMap<String, JsefValue>;
 
+/// Comments attached to values, keyed by the dotted path of the value they precede.
+/// 
+/// Each line of a comment is kept without its leading `#`. List items are addressed by their index.
+/// See [`parse_dict_with_comments`] and [`compose_dict_with_comments`].
+pub type JsefComments = HashMap<String, Vec<String>>;
+
 
 /// Parses a [`JsefValue`] from the input string.
 /// 
 /// Requires root lists and dicts to be enclosed in the appropriate brackets.
+/// Fails on input that contains only whitespace and comments, see [`parse_value_or_empty`].
 pub fn parse_value<S>(source: &S) -> JsefResult<JsefValue>
 where S: AsRef<str> + ?Sized {
-	Parser::new(source.as_ref()).parse_value_root()
+	parse_value_with(source, &ParseOpts::DEFAULT)
 }
 
 /// Parses a [`JsefList`] from the input string.
 /// 
 /// *Requires* the square brackets around the root list to be omitted.
+/// Input that contains only whitespace and comments results in an empty list.
 pub fn parse_list<S>(source: &S) -> JsefResult<JsefList>
 where S: AsRef<str> + ?Sized {
-	Parser::new(source.as_ref()).parse_list_root()
+	parse_list_with(source, &ParseOpts::DEFAULT)
 }
 
 /// Parses a [`JsefDict`] from the input string.
 /// 
 /// *Requires* the curly brackets around the root dict to be omitted.
+/// Input that contains only whitespace and comments results in an empty dict.
 pub fn parse_dict<S>(source: &S) -> JsefResult<JsefDict>
 where S: AsRef<str> + ?Sized {
-	Parser::new(source.as_ref()).parse_dict_root()
+	parse_dict_with(source, &ParseOpts::DEFAULT)
+}
+
+
+/// Like [`parse_value`], but input that contains only whitespace and comments
+/// results in an empty dict instead of an error.
+pub fn parse_value_or_empty<S>(source: &S) -> JsefResult<JsefValue>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), &ParseOpts::DEFAULT).parse_value_or_empty_root()
+}
+
+/// Parses a [`JsefValueCow`] from the input string,
+/// borrowing strings and keys from it whenever they contain no escape sequences.
+/// 
+/// Requires root lists and dicts to be enclosed in the appropriate brackets.
+pub fn parse_value_cow<S>(source: &S) -> JsefResult<JsefValueCow<'_>>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), &ParseOpts::DEFAULT).parse_value_root()
+}
+
+/// Another name for [`parse_value_cow`], producing a [`JsefValueRef`].
+pub fn parse_value_ref<S>(source: &S) -> JsefResult<JsefValueRef<'_>>
+where S: AsRef<str> + ?Sized {
+	parse_value_cow(source)
+}
+
+/// Parses a single [`JsefValue`] from the start of the input string,
+/// returning it along with the rest of the input and the position it ends at.
+/// 
+/// Leading whitespace and comments are skipped, anything after the value is left in the remainder.
+/// This allows parsing several values from one string one after another.
+pub fn parse_value_partial(source: &str) -> JsefResult<(JsefValue, &str, ParseEnd)> {
+	let (value, offset) = Parser::new(source, &ParseOpts::DEFAULT).parse_value_partial_root()?;
+	let (line, col) = count_line_col(&source[..offset]);
+	
+	Ok((value, &source[offset..], ParseEnd {line, col, offset}))
+}
+
+/// Like [`parse_value_partial`], but only returns the number of bytes consumed along with the value,
+/// so JSeF embedded at the start of other text can be skipped over.
+pub fn parse_value_prefix(source: &str) -> JsefResult<(JsefValue, usize)> {
+	let (value, _, end) = parse_value_partial(source)?;
+	Ok((value, end.offset))
+}
+
+/// Parses one [`JsefValue`] per line of `reader` using [`parse_value`].
+/// 
+/// Lines that are blank or only hold a comment are skipped.
+/// Errors report the line they occurred on and their byte offset within `reader`.
+/// Iteration stops after the first IO error, which is reported at col 0.
+pub fn parse_lines<R: BufRead>(mut reader: R) -> impl Iterator<Item = JsefResult<JsefValue>> {
+	let mut buf = String::new();
+	let mut number = 0;
+	let mut offset = 0;
+	let mut failed = false;
+	
+	iter::from_fn(move || {
+		if failed {
+			return None;
+		}
+		
+		loop {
+			buf.clear();
+			number += 1;
+			
+			let start = offset;
+			match reader.read_line(&mut buf) {
+				Ok(0) => return None,
+				Ok(len) => offset += len,
+				
+				Err(e) => {
+					failed = true;
+					return Some(Err(JsefErr::new(JsefErrType::Io(e.kind()), number, 0, start)));
+				},
+			}
+			
+			// Same line endings as BufRead::lines
+			let line = buf.strip_suffix('\n').unwrap_or(&buf);
+			let line = line.strip_suffix('\r').unwrap_or(line);
+			
+			let trimmed = line.trim_start();
+			if trimmed.is_empty() || trimmed.starts_with('#') {
+				continue;
+			}
+			
+			return Some(parse_value(line).map_err(|e| JsefErr {line: number, byte: start + e.byte, ..e}));
+		}
+	})
+}
+
+
+/// Parses a [`JsefValue`] from the input string using [`opts`](ParseOpts).
+/// 
+/// Requires root lists and dicts to be enclosed in the appropriate brackets.
+pub fn parse_value_with<S>(source: &S, opts: &ParseOpts) -> JsefResult<JsefValue>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), opts).parse_value_root()
+}
+
+/// Like [`parse_value_with`], but also returns [statistics](ParseInfo) about the parsed source.
+pub fn parse_value_with_info<S>(source: &S, opts: &ParseOpts) -> JsefResult<(JsefValue, ParseInfo)>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), opts).parse_value_root_info()
+}
+
+/// Parses a [`JsefList`] from the input string using [`opts`](ParseOpts).
+/// 
+/// *Requires* the square brackets around the root list to be omitted.
+pub fn parse_list_with<S>(source: &S, opts: &ParseOpts) -> JsefResult<JsefList>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), opts).parse_list_root()
+}
+
+/// Parses a [`JsefDict`] from the input string using [`opts`](ParseOpts).
+/// 
+/// *Requires* the curly brackets around the root dict to be omitted.
+pub fn parse_dict_with<S>(source: &S, opts: &ParseOpts) -> JsefResult<JsefDict>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), opts).parse_dict_root()
 }
 
 
@@ -60,27 +227,163 @@ where S: AsRef<str> + ?Sized {
 /// 
 /// Includes root brackets and acts as a counterpart to [`parse_value`].
 pub fn compose_value(value: &JsefValue, opts: &ComposeOpts) -> JsefResult<String> {
-	Composer::new(opts).compose_value_root(value)
+	let mut buf = String::new();
+	compose_value_into(value, opts, &mut buf)?;
+	Ok(buf)
 }
 
 /// Composes the input [`JsefList`] into a string formatted using [`opts`](ComposeOpts).
 /// 
 /// Omits root square brackets and acts as a counterpart to [`parse_list`].
 pub fn compose_list(list: &JsefList, opts: &ComposeOpts) -> JsefResult<String> {
-	Composer::new(opts).compose_list_root(list)
+	let mut buf = String::new();
+	compose_list_into(list, opts, &mut buf)?;
+	Ok(buf)
 }
 
 /// Composes the input [`JsefDict`] into a string formatted using [`opts`](ComposeOpts).
 /// 
 /// Omits root curly brackets and acts as a counterpart to [`parse_dict`].
 pub fn compose_dict(dict: &JsefDict, opts: &ComposeOpts) -> JsefResult<String> {
-	Composer::new(opts).compose_dict_root(dict)
+	let mut buf = String::new();
+	compose_dict_into(dict, opts, &mut buf)?;
+	Ok(buf)
+}
+
+/// Composes the input [`JsefValue`] like [`compose_value`], but appends the output to `buf`.
+/// 
+/// Error positions are relative to the start of the appended output,
+/// and `buf` is truncated back to its previous contents on errors.
+pub fn compose_value_into(value: &JsefValue, opts: &ComposeOpts, buf: &mut String) -> JsefResult {
+	let len = buf.len();
+	Composer::new(opts, &mut *buf).compose_value_root(value)
+		.map(drop)
+		.inspect_err(|_| buf.truncate(len))
+}
+
+/// Composes the input [`JsefList`] like [`compose_list`], but appends the output to `buf`.
+/// 
+/// Error positions are relative to the start of the appended output,
+/// and `buf` is truncated back to its previous contents on errors.
+pub fn compose_list_into(list: &JsefList, opts: &ComposeOpts, buf: &mut String) -> JsefResult {
+	let len = buf.len();
+	Composer::new(opts, &mut *buf).compose_list_root(list)
+		.map(drop)
+		.inspect_err(|_| buf.truncate(len))
+}
+
+/// Composes the input [`JsefDict`] like [`compose_dict`], but appends the output to `buf`.
+/// 
+/// Error positions are relative to the start of the appended output,
+/// and `buf` is truncated back to its previous contents on errors.
+pub fn compose_dict_into(dict: &JsefDict, opts: &ComposeOpts, buf: &mut String) -> JsefResult {
+	let len = buf.len();
+	Composer::new(opts, &mut *buf).compose_dict_root(dict)
+		.map(drop)
+		.inspect_err(|_| buf.truncate(len))
+}
+
+/// Parses a [`JsefDict`] like [`parse_dict`], but also returns the comments attached to the values within it.
+/// 
+/// Comments on the lines right before a pair or list item are attached to it.
+/// Comments after something else on the same line, within a pair, or at the end of a list or dict are dropped.
+pub fn parse_dict_with_comments<S>(source: &S) -> JsefResult<(JsefDict, JsefComments)>
+where S: AsRef<str> + ?Sized {
+	Parser::new(source.as_ref(), &ParseOpts::DEFAULT).parse_dict_root_comments()
+}
+
+/// Composes the input [`JsefDict`] like [`compose_dict`], but writes `comments` on the lines
+/// before the values they're attached to, as returned by [`parse_dict_with_comments`].
+/// 
+/// Comments are left out wherever the output is written on a single line,
+/// like when `indent` is `None` or within inlined collections.
+pub fn compose_dict_with_comments(dict: &JsefDict, opts: &ComposeOpts, comments: &JsefComments) -> JsefResult<String> {
+	Composer::new(opts, String::new()).with_comments(comments).compose_dict_root(dict)
+}
+
+/// Composes the input [`JsefValue`] like [`compose_value`], but passes the output to `sink` token by token.
+pub fn compose_value_sink<S: ComposeSink>(value: &JsefValue, opts: &ComposeOpts, sink: &mut S) -> JsefResult {
+	Composer::new(opts, sink).compose_value_root(value)?;
+	Ok(())
+}
+
+/// Composes the input [`JsefList`] like [`compose_list`], but passes the output to `sink` token by token.
+pub fn compose_list_sink<S: ComposeSink>(list: &JsefList, opts: &ComposeOpts, sink: &mut S) -> JsefResult {
+	Composer::new(opts, sink).compose_list_root(list)?;
+	Ok(())
+}
+
+/// Composes the input [`JsefDict`] like [`compose_dict`], but passes the output to `sink` token by token.
+pub fn compose_dict_sink<S: ComposeSink>(dict: &JsefDict, opts: &ComposeOpts, sink: &mut S) -> JsefResult {
+	Composer::new(opts, sink).compose_dict_root(dict)?;
+	Ok(())
+}
+
+/// Composes the input [`JsefValue`] like [`compose_value`], but writes the output to `writer`.
+/// 
+/// The output is written token by token, so unbuffered writers should be wrapped in a [`BufWriter`](std::io::BufWriter).
+/// IO errors fail with [`Io`](JsefErrType::Io) at the position of the token that couldn't be written.
+/// The writer is flushed at the end, and a failed flush is reported at the end of the output.
+pub fn compose_value_to<W: Write>(value: &JsefValue, opts: &ComposeOpts, writer: W) -> JsefResult {
+	Composer::new(opts, WriteSink::new(writer)).write_value_root(value)
+}
+
+/// Composes the input [`JsefList`] like [`compose_list`], but writes the output to `writer`.
+/// 
+/// The output is written token by token, so unbuffered writers should be wrapped in a [`BufWriter`](std::io::BufWriter).
+/// IO errors fail with [`Io`](JsefErrType::Io) at the position of the token that couldn't be written.
+/// The writer is flushed at the end, and a failed flush is reported at the end of the output.
+pub fn compose_list_to<W: Write>(list: &JsefList, opts: &ComposeOpts, writer: W) -> JsefResult {
+	Composer::new(opts, WriteSink::new(writer)).write_list_root(list)
+}
+
+/// Composes the input [`JsefDict`] like [`compose_dict`], but writes the output to `writer`.
+/// 
+/// The output is written token by token, so unbuffered writers should be wrapped in a [`BufWriter`](std::io::BufWriter).
+/// IO errors fail with [`Io`](JsefErrType::Io) at the position of the token that couldn't be written.
+/// The writer is flushed at the end, and a failed flush is reported at the end of the output.
+pub fn compose_dict_to<W: Write>(dict: &JsefDict, opts: &ComposeOpts, writer: W) -> JsefResult {
+	Composer::new(opts, WriteSink::new(writer)).write_dict_root(dict)
+}
+
+
+/// Returns the length in bytes of the string [`compose_value`] would produce, without composing it.
+pub fn composed_len(value: &JsefValue, opts: &ComposeOpts) -> JsefResult<usize> {
+	let counter = Composer::new(opts, LenCounter::new()).compose_value_root(value)?;
+	Ok(counter.len)
+}
+
+/// Like [`compose_value`], but allocates the exact capacity needed up front using [`composed_len`].
+/// 
+/// This composes `value` twice, which takes about twice as long as [`compose_value`],
+/// but never reallocates the resulting string or leaves it with spare capacity.
+pub fn compose_value_sized(value: &JsefValue, opts: &ComposeOpts) -> JsefResult<String> {
+	let len = composed_len(value, opts)?;
+	Composer::new(opts, String::with_capacity(len)).compose_value_root(value)
+}
+
+
+/// Returns whether `string` has to be enclosed in double quotes to be parsed back as a single value.
+/// 
+/// This is the rule the composer uses when [`quote_keys`](ComposeOpts::quote_keys)
+/// or [`quote_values`](ComposeOpts::quote_values) isn't set.
+pub fn needs_quotes(string: &str) -> bool {
+	string.is_empty() || string.chars().any(|c| !is_word_char(c))
 }
 
 
 fn is_word_char(c: char) -> bool {
 	const SPECIAL: [char; 8] = ['"', '=', '.', '{', '}', '[', ']', '#'];
-	!c.is_ascii_whitespace() && !SPECIAL.contains(&c)
+	!c.is_ascii_whitespace() && !c.is_control() && !SPECIAL.contains(&c)
+}
+
+
+fn join_path(path: &str, key: &str) -> String {
+	if path.is_empty() {
+		key.to_owned()
+	} else {
+		format!("{path}.{key}")
+	}
 }
 
 
@@ -99,3 +402,10 @@ fn count_line_col(string: &str) -> (usize, usize) {
 	
 	(line, col)
 }
+
+/// Stringifies a float the same way for the serde and json conversions,
+/// keeping the `.0` of whole numbers.
+#[cfg(any(feature = "serde", feature = "json"))]
+fn float_string(float: f64) -> String {
+	format!("{float:?}")
+}
@@ -0,0 +1,51 @@
+use crate::{JsefValue, join_path};
+
+
+/// A scalar that looks like a number but doesn't parse as one, such as `8o80`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuspiciousScalar {
+	/// Dotted path to the scalar, list items are addressed by their index.
+	pub path: String,
+	pub value: String,
+}
+
+
+/// Collects every scalar in `value` that looks like a number but can't be parsed as one.
+/// 
+/// A scalar looks like a number if it starts with a digit, optionally preceded by a sign.
+/// This is only a heuristic meant for linters, values are always stored as strings either way.
+pub fn lint_scalars(value: &JsefValue) -> Vec<SuspiciousScalar> {
+	let mut found = Vec::new();
+	let mut stack = vec![(String::new(), value)];
+	
+	while let Some((path, value)) = stack.pop() {
+		match value {
+			JsefValue::String(string) => if is_suspicious(string) {
+				found.push(SuspiciousScalar {path, value: string.clone()});
+			},
+			
+			JsefValue::List(list) => stack.extend(list
+				.iter()
+				.enumerate()
+				.rev()
+				.map(|(i, val)| (join_path(&path, &i.to_string()), val))
+			),
+			
+			JsefValue::Dict(dict) => stack.extend(dict
+				.iter()
+				.map(|(key, val)| (join_path(&path, key), val))
+			),
+		}
+	}
+	
+	found
+}
+
+
+fn is_suspicious(string: &str) -> bool {
+	let unsigned = string.strip_prefix(['+', '-']).unwrap_or(string);
+	
+	unsigned.starts_with(|c: char| c.is_ascii_digit())
+		&& string.parse::<i64>().is_err()
+		&& string.parse::<f64>().is_err()
+}
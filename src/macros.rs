@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+
+/// Builds a [`JsefValue`](crate::JsefValue), with a syntax similar to `serde_json::json!`.
+/// 
+/// - `[a, b]` is a list and `{key: a, "other key": b}` is a dict, both allow a trailing comma.
+/// - Literals like `"text"`, `1.50` or `true` become strings as they're written, except that
+///   string literals lose their quotes. Negative numbers have to be written as strings.
+/// - Identifiers are variables and parenthesized expressions are evaluated,
+///   both are converted with [`From`], so `(String::from("a"))`, `list` or `(-1i64)` work as values.
+/// 
+/// Dict keys can be identifiers, literals or parenthesized expressions that convert into a `String`.
+#[macro_export]
+macro_rules! jsef {
+	( @key $key:ident ) => {
+		::std::string::String::from(::std::stringify!($key))
+	};
+	
+	( @key $key:literal ) => {
+		$crate::__jsef_scalar(::std::stringify!($key), $key)
+	};
+	
+	( @key ($key:expr) ) => {
+		::std::string::String::from($key)
+	};
+	
+	( [ $($item:tt),* $(,)? ] ) => {
+		$crate::JsefValue::List(::std::vec![$($crate::jsef!($item)),*])
+	};
+	
+	( { $($key:tt : $val:tt),* $(,)? } ) => {{
+		#[allow(unused_mut)]
+		let mut dict = $crate::JsefDict::default();
+		$(dict.insert($crate::jsef!(@key $key), $crate::jsef!($val));)*
+		$crate::JsefValue::Dict(dict)
+	}};
+	
+	( $lit:literal ) => {
+		$crate::JsefValue::String($crate::__jsef_scalar(::std::stringify!($lit), $lit))
+	};
+	
+	( $var:ident ) => {
+		$crate::JsefValue::from($var)
+	};
+	
+	( ($value:expr) ) => {
+		$crate::JsefValue::from($value)
+	};
+}
+
+
+/// Returns the string a literal stands for, given its source `text` and its `value`.
+/// 
+/// Other literals are kept as written, since `1.50` would be displayed as `1.5`.
+#[doc(hidden)]
+pub fn scalar(text: &str, value: impl Display) -> String {
+	if text.starts_with(['"', '\'', 'r']) {
+		value.to_string()
+	} else {
+		text.to_owned()
+	}
+}
@@ -0,0 +1,27 @@
+use crate::JsefValue;
+
+
+/// Rewrites every scalar in `value` that matches one of the `truthy` or `falsy` spellings
+/// to `"true"` or `"false"` respectively, ignoring ASCII case.
+/// 
+/// Spellings found in both slices count as truthy. Dict keys are left alone.
+pub fn canonicalize_bools(value: &mut JsefValue, truthy: &[&str], falsy: &[&str]) {
+	let matches = |spellings: &[&str], string: &str| {
+		spellings.iter().any(|s| s.eq_ignore_ascii_case(string))
+	};
+	
+	let mut stack = vec![value];
+	
+	while let Some(value) = stack.pop() {
+		match value {
+			JsefValue::String(string) => if matches(truthy, string) {
+				"true".clone_into(string);
+			} else if matches(falsy, string) {
+				"false".clone_into(string);
+			},
+			
+			JsefValue::List(list) => stack.extend(list.iter_mut()),
+			JsefValue::Dict(dict) => stack.extend(dict.values_mut()),
+		}
+	}
+}
@@ -1,296 +1,940 @@
+use std::{borrow::Cow, mem};
+
+use crash::CrashMap;
+
 use crate::{
-	JsefValue, JsefList, JsefDict,
-	JsefErrType::{self, *},
-	JsefErr, JsefResult,
-	DEPTH_LIMIT,
-	is_word_char, count_line_col,
+	JsefValue, JsefValueCow, JsefList, JsefDict, JsefKind, JsefComments,
+	JsefErrType::*,
+	JsefResult,
+	DEPTH_LIMIT, KEY_PREFIX_LEN,
+	is_word_char, join_path,
+	lex::Lexer,
 };
 
 
+/// Options for parsing strings into [`JsefValue`]s.
+#[derive(Debug, Clone)]
+pub struct ParseOpts {
+	/// Whether commas between list items and dict pairs should be skipped like whitespace.
+	/// Leading, trailing and repeated commas are accepted as well.
+	/// Commas are never part of unquoted words when this is set.
+	pub commas_ok: bool,
+	
+	/// Whether backslashes in quoted strings should be kept literally,
+	/// except for `\"` which still produces a double quote.
+	/// 
+	/// Escaped output of the default composer won't round-trip when parsed this way,
+	/// compose with [`ComposeOpts::raw_strings`](crate::ComposeOpts::raw_strings) instead.
+	pub raw_strings: bool,
+	
+	/// Whether assigning a value of a different kind to an existing path should fail
+	/// with [`TypeConflict`](crate::JsefErrType::TypeConflict) instead of replacing it.
+	/// This includes folding through a key that isn't a dict, like `a = x` followed by `a.b = y`.
+	pub strict_types: bool,
+	
+	/// The maximum length of a single key in bytes, after escapes are decoded.
+	/// Longer keys fail with [`KeyTooLong`](crate::JsefErrType::KeyTooLong).
+	/// Each segment of a path counts as its own key.
+	pub max_key_len: Option<usize>,
+	
+	/// Which unescaped control chars quoted strings may contain,
+	/// others fail with [`RawControl`](crate::JsefErrType::RawControl).
+	pub raw_controls: RawControls,
+	
+	/// How deeply lists and dicts may be nested before parsing fails with [`MaxDepth`](crate::JsefErrType::MaxDepth).
+	/// Parsing doesn't recurse, but composing or dropping very deeply nested values still might overflow the stack.
+	/// 
+	/// # Security
+	/// 
+	/// The default of [`DEPTH_LIMIT`] bounds nesting to 256 levels.
+	/// Setting this to `usize::MAX` effectively lifts the limit, which is only safe for trusted input:
+	/// untrusted input can then nest deeply enough to overflow the stack in the code that still recurses
+	/// once per level after parsing:
+	/// 
+	/// - Dropping, cloning, comparing, hashing and [`Debug`] formatting of [`JsefValue`](crate::JsefValue)s,
+	///   which use the standard or derived implementations.
+	/// - Merging dicts, as done by [`merge`](crate::JsefValue::merge), [`merge_str`](crate::JsefValue::merge_str)
+	///   and [`load_with_includes`](crate::load_with_includes).
+	/// - [`JsefValueCow::into_owned`](crate::JsefValueCow::into_owned) and [`to_owned`](crate::JsefValueCow::to_owned).
+	/// - [`assert_jsef_eq!`](crate::assert_jsef_eq) and the `Serialize` and `Deserialize` impls of the `serde` feature.
+	/// 
+	/// Composing recurses too, but fails with [`MaxDepth`](crate::JsefErrType::MaxDepth)
+	/// past [`DEPTH_LIMIT`] instead of overflowing, whatever this is set to.
+	pub max_depth: usize,
+	
+	/// Whether assigning to a key that already holds a value should fail with
+	/// [`DuplicateKey`](crate::JsefErrType::DuplicateKey) instead of replacing it.
+	/// Folding through an existing dict, like `a.b = x` followed by `a.c = y`, doesn't count,
+	/// but folding through any other value does.
+	pub reject_duplicates: bool,
+	
+	/// Starts a comment that runs until the end of the line, comments are disabled when it's empty.
+	/// 
+	/// Its first char can't appear in unquoted words, so with `"//"` words like `a/b` need quotes.
+	/// `#` is an ordinary word char unless the lead starts with it.
+	pub comment_lead: &'static str,
+	
+	/// Whether unknown escape sequences like `\q` should fail with [`InvalidEscape`](crate::JsefErrType::InvalidEscape)
+	/// instead of producing the escaped char as-is. Has no effect with `raw_strings`.
+	pub strict_escapes: bool,
+	
+	/// How many lines at the start of the source are ignored entirely, such as a shebang line.
+	/// Error positions still count them.
+	pub skip_lines: usize,
+}
+
+
+/// Which control chars may appear unescaped within quoted strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawControls {
+	/// Any char is accepted as-is.
+	Allow,
+	/// Only NUL has to be written as `\0`.
+	RejectNul,
+	/// None of the C0 controls `U+0000..=U+001F` are accepted, which includes tabs and newlines.
+	RejectC0,
+}
+
+impl RawControls {
+	fn rejects(self, c: char) -> bool {
+		match self {
+			Self::Allow => false,
+			Self::RejectNul => c == '\0',
+			Self::RejectC0 => c <= '\u{1f}',
+		}
+	}
+}
+
+impl ParseOpts {
+	/// The options used by [`parse_value`](crate::parse_value) and friends.
+	/// 
+	/// # Values
+	/// - `commas_ok`: `false`
+	/// - `raw_strings`: `false`
+	/// - `strict_types`: `false`
+	/// - `max_key_len`: `None`
+	/// - `raw_controls`: `RawControls::Allow`
+	/// - `max_depth`: [`DEPTH_LIMIT`]
+	/// - `reject_duplicates`: `false`
+	/// - `comment_lead`: `"#"`
+	/// - `strict_escapes`: `false`
+	/// - `skip_lines`: `0`
+	pub const DEFAULT: Self = Self {
+		commas_ok: false,
+		raw_strings: false,
+		strict_types: false,
+		max_key_len: None,
+		raw_controls: RawControls::Allow,
+		max_depth: DEPTH_LIMIT,
+		reject_duplicates: false,
+		comment_lead: "#",
+		strict_escapes: false,
+		skip_lines: 0,
+	};
+}
+
+impl ParseOpts {
+	pub const fn commas_ok(mut self, value: bool) -> Self {
+		self.commas_ok = value;
+		self
+	}
+	
+	pub const fn raw_strings(mut self, value: bool) -> Self {
+		self.raw_strings = value;
+		self
+	}
+	
+	pub const fn strict_types(mut self, value: bool) -> Self {
+		self.strict_types = value;
+		self
+	}
+	
+	pub const fn max_key_len(mut self, value: usize) -> Self {
+		self.max_key_len = Some(value);
+		self
+	}
+	
+	pub const fn no_max_key_len(mut self) -> Self {
+		self.max_key_len = None;
+		self
+	}
+	
+	pub const fn raw_controls(mut self, value: RawControls) -> Self {
+		self.raw_controls = value;
+		self
+	}
+	
+	pub const fn max_depth(mut self, value: usize) -> Self {
+		self.max_depth = value;
+		self
+	}
+	
+	pub const fn reject_duplicates(mut self, value: bool) -> Self {
+		self.reject_duplicates = value;
+		self
+	}
+	
+	pub const fn comment_lead(mut self, value: &'static str) -> Self {
+		self.comment_lead = value;
+		self
+	}
+	
+	pub const fn strict_escapes(mut self, value: bool) -> Self {
+		self.strict_escapes = value;
+		self
+	}
+	
+	pub const fn skip_lines(mut self, value: usize) -> Self {
+		self.skip_lines = value;
+		self
+	}
+	
+	/// Returns whether `c` has to be escaped within quoted strings.
+	pub(crate) fn rejects_raw(&self, c: char) -> bool {
+		self.raw_controls.rejects(c)
+	}
+	
+	pub(crate) fn is_word_char(&self, c: char) -> bool {
+		let base = is_word_char(c) || (c == '#' && !self.comment_lead.starts_with('#'));
+		base && !self.comment_lead.starts_with(c) && !(self.commas_ok && c == ',')
+	}
+}
+
+
+/// Statistics about a successful parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseInfo {
+	/// The deepest nesting level reached, as counted against the depth limit.
+	pub peak_depth: usize,
+	/// The length of the parsed source in bytes.
+	pub bytes: usize,
+	/// The number of values parsed, including nested lists and dicts.
+	pub items: usize,
+}
+
+
+/// The position right after a value parsed by [`parse_value_partial`](crate::parse_value_partial).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseEnd {
+	pub line: usize,
+	pub col: usize,
+	/// The byte offset into the source.
+	pub offset: usize,
+}
+
+
+/// A step of parsing reported by [`parse_events`](crate::parse_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'s> {
+	/// A quoted string or an unquoted word in the place of a value.
+	StringValue(Cow<'s, str>),
+	ListStart,
+	ListEnd,
+	DictStart,
+	DictEnd,
+	/// The key of a pair, followed by the events of its value.
+	Key(Cow<'s, str>),
+}
+
+impl Event<'_> {
+	/// Copies borrowed strings so the event can outlive the source.
+	pub fn into_owned(self) -> Event<'static> {
+		match self {
+			Self::StringValue(s) => Event::StringValue(Cow::Owned(s.into_owned())),
+			Self::Key(s) => Event::Key(Cow::Owned(s.into_owned())),
+			Self::ListStart => Event::ListStart,
+			Self::ListEnd => Event::ListEnd,
+			Self::DictStart => Event::DictStart,
+			Self::DictEnd => Event::DictEnd,
+		}
+	}
+}
+
+
+/// A tree of values the parser can build.
+pub(crate) trait Node<'s>: Sized {
+	type Map: Default;
+	
+	fn from_string(string: Cow<'s, str>) -> Self;
+	fn from_list(list: Vec<Self>) -> Self;
+	fn from_dict(dict: Self::Map) -> Self;
+	
+	fn kind(&self) -> JsefKind;
+	fn kind_at(dict: &Self::Map, key: &str) -> Option<JsefKind>;
+	
+	/// Returns the dict at `key`, inserting or replacing the value there if it isn't a dict.
+	fn path_dict<'d>(dict: &'d mut Self::Map, key: Cow<'s, str>) -> &'d mut Self::Map;
+	fn insert(dict: &mut Self::Map, key: Cow<'s, str>, value: Self);
+}
+
+impl<'s> Node<'s> for JsefValue {
+	type Map = JsefDict;
+	
+	fn from_string(string: Cow<'s, str>) -> Self {
+		Self::String(string.into_owned())
+	}
+	
+	fn from_list(list: JsefList) -> Self {
+		Self::List(list)
+	}
+	
+	fn from_dict(dict: JsefDict) -> Self {
+		Self::Dict(dict)
+	}
+	
+	fn kind(&self) -> JsefKind {
+		self.kind()
+	}
+	
+	fn kind_at(dict: &JsefDict, key: &str) -> Option<JsefKind> {
+		dict.get(key).map(Self::kind)
+	}
+	
+	fn path_dict<'d>(dict: &'d mut JsefDict, key: Cow<'s, str>) -> &'d mut JsefDict {
+		let value = dict
+			.entry(key.into_owned())
+			.or_insert_with(Self::new_dict);
+		
+		match value {
+			Self::Dict(d) => d,
+			
+			val => {
+				*val = Self::new_dict();
+				// unwrap should be safe, val was just replaced with a JsefValue::Dict
+				val.as_dict_mut().unwrap()
+			},
+		}
+	}
+	
+	fn insert(dict: &mut JsefDict, key: Cow<'s, str>, value: Self) {
+		dict.insert(key.into_owned(), value);
+	}
+}
+
+impl<'s> Node<'s> for JsefValueCow<'s> {
+	type Map = CrashMap<Cow<'s, str>, Self>;
+	
+	fn from_string(string: Cow<'s, str>) -> Self {
+		Self::String(string)
+	}
+	
+	fn from_list(list: Vec<Self>) -> Self {
+		Self::List(list)
+	}
+	
+	fn from_dict(dict: Self::Map) -> Self {
+		Self::Dict(dict)
+	}
+	
+	fn kind(&self) -> JsefKind {
+		self.kind()
+	}
+	
+	fn kind_at(dict: &Self::Map, key: &str) -> Option<JsefKind> {
+		dict.get(key).map(Self::kind)
+	}
+	
+	fn path_dict<'d>(dict: &'d mut Self::Map, key: Cow<'s, str>) -> &'d mut Self::Map {
+		let value = dict
+			.entry(key)
+			.or_insert_with(|| Self::Dict(Default::default()));
+		
+		match value {
+			Self::Dict(d) => d,
+			
+			val => {
+				*val = Self::Dict(Default::default());
+				// unwrap should be safe, val was just replaced with a JsefValueCow::Dict
+				val.as_dict_mut().unwrap()
+			},
+		}
+	}
+	
+	fn insert(dict: &mut Self::Map, key: Cow<'s, str>, value: Self) {
+		dict.insert(key, value);
+	}
+}
+
+
 #[derive(Debug)]
-pub(crate) struct Parser<'s> {
-	source: &'s str,
-	peek: Option<char>,
-	idx: usize,
+pub(crate) struct Parser<'s, 'o> {
+	opts: &'o ParseOpts,
+	lex: Lexer<'s, 'o>,
 	depth: usize,
+	peak_depth: usize,
+	items: usize,
+	/// Comments attached to the values parsed so far, only collected when set.
+	comments: Option<JsefComments>,
+	/// The dotted path of the value being parsed, only kept track of when collecting comments.
+	path: String,
 }
 
-impl<'s> Parser<'s> {
-	pub(crate) fn new(source: &'s str) -> Self {
-		let peek = source.chars().next();
-		
+impl<'s, 'o> Parser<'s, 'o> {
+	pub(crate) fn new(source: &'s str, opts: &'o ParseOpts) -> Self {
 		Self {
-			idx: 0, depth: 0,
-			source, peek,
+			lex: Lexer::new(source, opts),
+			depth: 0, peak_depth: 0, items: 0,
+			comments: None,
+			path: String::new(),
+			opts,
 		}
 	}
 	
-	pub(crate) fn parse_value_root(mut self) -> JsefResult<JsefValue> {
-		self.skip_whitespace();
+	pub(crate) fn parse_value_root<N: Node<'s>>(self) -> JsefResult<N> {
+		let (value, _) = self.parse_value_root_info()?;
+		Ok(value)
+	}
+	
+	pub(crate) fn parse_value_root_info<N: Node<'s>>(mut self) -> JsefResult<(N, ParseInfo)> {
+		self.lex.skip_whitespace();
 		let value = self.parse_value()?;
-		self.skip_whitespace();
-		self.assert_eof()?;
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
 		
-		Ok(value)
+		let info = ParseInfo {
+			peak_depth: self.peak_depth,
+			bytes: self.lex.source.len(),
+			items: self.items,
+		};
+		
+		Ok((value, info))
+	}
+	
+	/// Parses a single value without asserting EOF, returning the byte offset right after it.
+	pub(crate) fn parse_value_partial_root<N: Node<'s>>(mut self) -> JsefResult<(N, usize)> {
+		self.lex.skip_whitespace();
+		let value = self.parse_value()?;
+		Ok((value, self.lex.idx))
+	}
+	
+	pub(crate) fn parse_value_or_empty_root<N: Node<'s>>(mut self) -> JsefResult<N> {
+		self.lex.skip_whitespace();
+		
+		if self.lex.peek().is_none() {
+			Ok(N::from_dict(N::Map::default()))
+		} else {
+			self.parse_value_root()
+		}
 	}
 	
 	pub(crate) fn parse_list_root(mut self) -> JsefResult<JsefList> {
-		let list = self.parse_list(true)?;
-		self.skip_whitespace();
-		self.assert_eof()?;
+		let list = self.parse_list()?;
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
 		
 		Ok(list)
 	}
 	
 	pub(crate) fn parse_dict_root(mut self) -> JsefResult<JsefDict> {
-		let dict = self.parse_dict(true)?;
-		self.skip_whitespace();
-		self.assert_eof()?;
+		let dict = self.parse_dict::<JsefValue>()?;
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
 		
 		Ok(dict)
 	}
-}
-
-impl Parser<'_> {
-	fn err(&self, err: JsefErrType) -> JsefErr {
-		let (line, col) = count_line_col(&self.source[..self.idx]);
-		JsefErr::new(err, line, col)
+	
+	/// Parses the items of a root list like [`Self::parse_list_root`], appending them to `list`.
+	pub(crate) fn parse_list_root_into(mut self, list: JsefList) -> JsefResult<JsefList> {
+		let frame = Frame {items: Items::List(list), root: true, base: self.path.len()};
+		
+		let Items::List(list) = self.parse_nested(frame)?.items else {
+			unreachable!("the bottom frame is a list");
+		};
+		
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
+		Ok(list)
 	}
 	
-	fn slice(&self) -> &str {
-		&self.source[self.idx..]
+	/// Parses the pairs of a root dict like [`Self::parse_dict_root`], adding them to `dict`
+	/// as if they came after the pairs already in it.
+	pub(crate) fn parse_dict_root_into(mut self, dict: JsefDict) -> JsefResult<JsefDict> {
+		let frame = Frame {items: Items::<JsefValue>::Dict(dict, None), root: true, base: self.path.len()};
+		
+		let Items::Dict(dict, _) = self.parse_nested(frame)?.items else {
+			unreachable!("the bottom frame is a dict");
+		};
+		
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
+		Ok(dict)
 	}
 	
-	fn peek(&self) -> Option<char> {
-		self.peek
+	/// Parses a root dict and returns the `#include "..."` directives found in its comments.
+	pub(crate) fn parse_dict_root_includes(mut self) -> JsefResult<(JsefDict, Vec<(String, usize)>)> {
+		self.lex.includes = Some(Vec::new());
+		let dict = self.parse_dict::<JsefValue>()?;
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
+		
+		Ok((dict, self.lex.includes.unwrap_or_default()))
 	}
 	
-	fn next(&mut self) -> Option<char> {
-		let prev = self.peek;
-		self.idx = self.source.ceil_char_boundary(self.idx + 1);
-		self.peek = self.slice().chars().next();
+	/// Parses a root dict and returns the comments attached to the values within it.
+	pub(crate) fn parse_dict_root_comments(mut self) -> JsefResult<(JsefDict, JsefComments)> {
+		self.lex.comments = Some(Vec::new());
+		self.comments = Some(JsefComments::new());
+		
+		let dict = self.parse_dict::<JsefValue>()?;
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()?;
 		
-		prev
+		Ok((dict, self.comments.unwrap_or_default()))
 	}
 	
-	fn take(&mut self) -> JsefResult<char> {
-		self.next().ok_or_else(|| self.err(Unexpected(None)))
+	/// Parses the pairs of a root dict, passing [`Event`]s to `emit` instead of building it.
+	/// The root dict itself isn't reported, so its pairs can be parsed in pieces.
+	pub(crate) fn parse_dict_root_events<F: FnMut(Event<'s>)>(mut self, mut emit: F) -> JsefResult {
+		let mut stack = vec![EventFrame {list: false, root: true, key: None, folds: 0}];
+		
+		self.parse_events(&mut stack, &mut emit).map_err(|mut e| {
+			// Same as in parse_nested
+			for key in stack.iter().rev().filter_map(|frame| frame.key.as_ref()) {
+				e = e.in_key(key);
+			}
+			
+			e
+		})?;
+		
+		self.lex.skip_whitespace();
+		self.lex.assert_eof()
+	}
+}
+
+impl<'s> Parser<'s, '_> {
+	fn parse_key(&mut self) -> JsefResult<Cow<'s, str>> {
+		let start = self.lex.idx;
+		let key = self.lex.parse_ident()?;
+		
+		match self.opts.max_key_len {
+			Some(max) if key.len() > max => {
+				let prefix = key.chars().take(KEY_PREFIX_LEN).collect();
+				Err(self.lex.err_at(KeyTooLong(prefix), start))
+			},
+			
+			_ => Ok(key),
+		}
 	}
 	
-	fn next_while<F>(&mut self, mut pred: F) -> &str
-	where F: FnMut(char) -> bool {
-		let slice = self.slice();
-		let len = slice.len();
-		let start = self.idx;
+	/// Parses the keys and `=` of a pair, leaving its value to be parsed.
+	fn begin_pair<N: Node<'s>>(&mut self, mut dict: &mut N::Map) -> JsefResult<Pair<'s>> {
+		let start = self.lex.idx;
+		let mut path = String::new();
+		let mut keys = Vec::new();
 		
-		let find = slice
-			.char_indices()
-			.find(|(_, c)| !pred(*c));
+		let leading = self.lex.comments.as_mut().map(mem::take);
 		
-		if let Some((i, c)) = find {
-			self.peek = Some(c);
-			self.idx += i;
-		} else {
-			self.peek = None;
-			self.idx += len;
+		let mut key_start = start;
+		let mut key = self.parse_key()?;
+		self.push_path(&key);
+		self.lex.skip_whitespace();
+		
+		while self.lex.try_eat('.') {
+			self.check_kind::<N>(dict, &key, JsefKind::Dict, &mut path, start)?;
+			self.check_duplicate::<N>(dict, &key, true, key_start)?;
+			dict = N::path_dict(dict, key.clone());
+			keys.push(key);
+			
+			self.lex.skip_whitespace();
+			key_start = self.lex.idx;
+			key = self.parse_key()?;
+			self.push_path(&key);
+			self.lex.skip_whitespace();
 		}
 		
-		&self.source[start..self.idx]
+		self.lex.eat('=').map_err(|e| e.in_key(&key))?;
+		self.lex.skip_whitespace();
+		
+		// Comments within the pair itself are dropped
+		if let Some(pending) = &mut self.lex.comments {
+			pending.clear();
+			self.attach_comments(leading.unwrap_or_default());
+		}
+		
+		keys.push(key);
+		Ok(Pair {keys, start, key_start, path})
 	}
 	
-	fn eat(&mut self, c: char) -> JsefResult {
-		// Can't call Self::next right away,
-		// since that would screw up the error line-column reporting
-		match self.peek() {
-			Some(p) if p == c => {
-				self.next();
-				Ok(())
-			},
-			
-			p => Err(self.err(Mismatch(c, p))),
+	/// Assigns `value` to the keys of `pair`.
+	fn end_pair<N: Node<'s>>(&self, mut dict: &mut N::Map, pair: Pair<'s>, value: N) -> JsefResult {
+		let Pair {mut keys, start, key_start, mut path} = pair;
+		// unwrap should be safe, begin_pair always pushes the last key
+		let key = keys.pop().unwrap();
+		
+		// The dicts along the way were already created by begin_pair
+		for key in keys {
+			dict = N::path_dict(dict, key);
 		}
+		
+		self.check_kind::<N>(dict, &key, value.kind(), &mut path, start)?;
+		self.check_duplicate::<N>(dict, &key, false, key_start)?;
+		N::insert(dict, key, value);
+		
+		Ok(())
 	}
 	
-	fn try_eat(&mut self, c: char) -> bool {
-		if self.peek() == Some(c) {
-			self.next();
-			true
-		} else {
-			false
+	/// Appends `key` to the path of the value being parsed when collecting comments.
+	fn push_path(&mut self, key: &str) {
+		if self.comments.is_none() {
+			return;
+		}
+		
+		if !self.path.is_empty() {
+			self.path.push('.');
 		}
+		
+		self.path.push_str(key);
 	}
 	
-	fn assert_eof(&self) -> JsefResult {
-		match self.peek() {
-			Some(p) => Err(self.err(NotEof(p))),
-			None => Ok(()),
+	/// Attaches `lines` to the value at the current path.
+	fn attach_comments(&mut self, lines: Vec<&str>) {
+		if let Some(comments) = &mut self.comments
+		&& !lines.is_empty() {
+			let lines = lines.into_iter().map(str::to_owned).collect();
+			comments.insert(self.path.clone(), lines);
 		}
 	}
 	
-	fn skip_whitespace(&mut self) {
-		while let Some(c) = self.peek() {
-			if c.is_ascii_whitespace() {
-				self.next_while(|c| c.is_ascii_whitespace());
-				continue;
-			}
-			
-			if c == '#' {
-				self.next_while(|c| c != '\n');
-				continue;
-			}
+	/// Fails if [`strict_types`](ParseOpts::strict_types) is set and `key` already holds something
+	/// other than `found`, appending `key` to `path` of the pair starting at `start` otherwise.
+	fn check_kind<N: Node<'s>>(
+		&self,
+		dict: &N::Map, key: &str, found: JsefKind,
+		path: &mut String, start: usize,
+	) -> JsefResult {
+		if !self.opts.strict_types {
+			return Ok(());
+		}
+		
+		*path = join_path(path, key);
+		
+		match N::kind_at(dict, key) {
+			Some(expected) if expected != found => {
+				let path = mem::take(path);
+				Err(self.lex.err_at(TypeConflict {path, expected, found}, start))
+			},
 			
-			break;
+			_ => Ok(()),
 		}
 	}
 	
-	fn parse_word(&mut self) -> JsefResult<String> {
-		let slice = self.next_while(is_word_char);
+	/// Fails if [`reject_duplicates`](ParseOpts::reject_duplicates) is set and `key` already holds a value,
+	/// unless it's a dict that is `folding` through. `start` is where the key begins.
+	fn check_duplicate<N: Node<'s>>(&self, dict: &N::Map, key: &str, folding: bool, start: usize) -> JsefResult {
+		if !self.opts.reject_duplicates {
+			return Ok(());
+		}
 		
-		if !slice.is_empty() {
-			Ok(slice.to_owned())
-		} else {
-			Err(self.err(Unexpected(self.peek())))
+		match N::kind_at(dict, key) {
+			Some(JsefKind::Dict) if folding => Ok(()),
+			Some(_) => Err(self.lex.err_at(DuplicateKey(key.to_owned()), start)),
+			None => Ok(()),
 		}
 	}
 	
-	fn parse_escape(&mut self) -> JsefResult<char> {
-		self.eat('\\')?;
+	/// Parses a scalar, or opens the list or dict that starts here.
+	fn begin_value<N: Node<'s>>(&mut self) -> JsefResult<Begun<'s, N>> {
+		self.items += 1;
 		
-		match self.take()? {
-			'n' => Ok('\n'),
-			't' => Ok('\t'),
-			'r' => Ok('\r'),
-			'0' => Ok('\0'),
+		match self.lex.peek() {
+			Some('{') => Ok(Begun::Frame(self.open(Items::Dict(N::Map::default(), None), '{')?)),
+			Some('[') => Ok(Begun::Frame(self.open(Items::List(Vec::new()), '[')?)),
+			Some('"') => Ok(Begun::Value(N::from_string(self.lex.parse_string()?))),
+			Some(_) => Ok(Begun::Value(N::from_string(self.lex.parse_word()?))),
 			
-			c => Ok(c),
+			p => Err(self.lex.err(Unexpected(p))),
 		}
 	}
 	
-	fn parse_string(&mut self) -> JsefResult<String> {
-		let mut string = String::new();
-		self.eat('"')?;
-		
-		loop {
-			let slice = self.next_while(|c| c != '"' && c != '\\');
-			string.push_str(slice);
-			
-			if self.peek() == Some('\\') {
-				let c = self.parse_escape()?;
-				string.push(c);
-			} else {
-				self.eat('"')?;
-				break;
-			}
+	fn open<N: Node<'s>>(&mut self, items: Items<'s, N>, open: char) -> JsefResult<Frame<'s, N>> {
+		self.enter(open)?;
+		Ok(Frame {items, root: false, base: self.path.len()})
+	}
+	
+	/// Eats the `open` bracket of a nested collection, counting it against the depth limit.
+	fn enter(&mut self, open: char) -> JsefResult {
+		self.depth += 1;
+		if self.depth > self.opts.max_depth {
+			return Err(self.lex.err(MaxDepth));
 		}
 		
-		Ok(string)
+		self.peak_depth = self.peak_depth.max(self.depth);
+		self.lex.nested = true;
+		self.lex.eat(open)
 	}
 	
-	fn parse_ident(&mut self) -> JsefResult<String> {
-		match self.peek() {
-			Some('"') => self.parse_string(),
-			_ => self.parse_word(),
+	fn close<N: Node<'s>>(&mut self, frame: Frame<'s, N>) -> JsefResult<Frame<'s, N>> {
+		// Comments at the end of a collection don't belong to any value
+		if let Some(pending) = &mut self.lex.comments {
+			pending.clear();
+		}
+		
+		if !frame.root {
+			self.depth -= 1;
+			self.lex.nested = self.depth > 0;
+			self.lex.eat(frame.items.close())?;
 		}
+		
+		Ok(frame)
 	}
 	
-	fn parse_pair(&mut self, mut dict: &mut JsefDict) -> JsefResult {
-		let mut key = self.parse_ident()?;
-		self.skip_whitespace();
+	/// Parses the items of `bottom` and everything nested within them until `bottom` is closed.
+	/// 
+	/// Nested lists and dicts are kept on an explicit stack rather than recursed into,
+	/// so deeply nested input doesn't use up the call stack.
+	fn parse_nested<N: Node<'s>>(&mut self, bottom: Frame<'s, N>) -> JsefResult<Frame<'s, N>> {
+		let mut stack = vec![bottom];
 		
-		while self.try_eat('.') {
-			let value = dict
-				.entry(key)
-				.or_insert_with(JsefValue::new_dict);
+		self.parse_frames(&mut stack).map_err(|mut e| {
+			// The innermost pair whose value was being parsed comes first
+			for frame in stack.iter().rev() {
+				if let Items::Dict(_, Some(pair)) = &frame.items {
+					// unwrap should be safe, pairs always have at least one key
+					e = e.in_key(pair.keys.last().unwrap());
+				}
+			}
 			
-			match value {
-				JsefValue::Dict(d) => dict = d,
+			e
+		})
+	}
+	
+	fn parse_frames<N: Node<'s>>(&mut self, stack: &mut Vec<Frame<'s, N>>) -> JsefResult<Frame<'s, N>> {
+		loop {
+			// unwrap should be safe, the bottom frame is returned as soon as it's popped
+			let frame = stack.last_mut().unwrap();
+			self.lex.skip_separators();
+			
+			let value = match self.lex.peek() {
+				Some(c) if frame.items.accepts(c, self.opts) => {
+					self.begin_item(frame)?;
+					
+					match self.begin_value()? {
+						Begun::Value(value) => value,
+						Begun::Frame(frame) => {
+							stack.push(frame);
+							continue;
+						},
+					}
+				},
 				
-				val => {
-					*val = JsefValue::new_dict();
-					// unwrap should be safe, val was just replaced with a JsefValue::Dict
-					dict = val.as_dict_mut().unwrap();
+				_ => {
+					// unwrap should be safe, see above
+					let frame = self.close(stack.pop().unwrap())?;
+					
+					if stack.is_empty() {
+						return Ok(frame);
+					}
+					
+					frame.items.into_node()
 				},
-			}
+			};
 			
-			self.skip_whitespace();
-			key = self.parse_ident()?;
-			self.skip_whitespace();
+			// unwrap should be safe, the stack was just checked to not be empty
+			self.end_item(stack.last_mut().unwrap(), value)?;
+		}
+	}
+	
+	fn begin_item<N: Node<'s>>(&mut self, frame: &mut Frame<'s, N>) -> JsefResult {
+		match &mut frame.items {
+			Items::List(list) => if let Some(pending) = &mut self.lex.comments {
+				let leading = mem::take(pending);
+				self.push_path(&list.len().to_string());
+				self.attach_comments(leading);
+			},
+			
+			Items::Dict(dict, pair) => *pair = Some(self.begin_pair::<N>(dict)?),
 		}
 		
-		self.eat('=')?;
-		self.skip_whitespace();
-		
-		let value = self.parse_value()?;
-		dict.insert(key, value);
+		Ok(())
+	}
+	
+	fn end_item<N: Node<'s>>(&mut self, frame: &mut Frame<'s, N>, value: N) -> JsefResult {
+		match &mut frame.items {
+			Items::List(list) => list.push(value),
+			
+			Items::Dict(dict, pair) => {
+				// unwrap should be safe, begin_item always starts a pair within dicts
+				let pair = pair.take().unwrap();
+				self.end_pair(dict, pair, value)?;
+			},
+		}
 		
+		self.path.truncate(frame.base);
 		Ok(())
 	}
 	
-	fn parse_many<P, F>(
-		&mut self,
-		root: bool, open: char, close: char,
-		mut pred: P, mut func: F,
-	) -> JsefResult
-	where
-		P: FnMut(char) -> bool,
-		F: FnMut(&mut Self) -> JsefResult,
-	{
-		if !root {
-			self.depth += 1;
-			if self.depth > DEPTH_LIMIT {
-				return Err(self.err(MaxDepth));
+	/// Like [`Self::parse_frames`], but only reports what it finds.
+	fn parse_events<F: FnMut(Event<'s>)>(&mut self, stack: &mut Vec<EventFrame<'s>>, emit: &mut F) -> JsefResult {
+		loop {
+			// unwrap should be safe, parsing ends as soon as the bottom frame is popped
+			let frame = stack.last_mut().unwrap();
+			self.lex.skip_separators();
+			
+			match self.lex.peek() {
+				Some(c) if accepts(frame.list, c, self.opts) => {
+					if !frame.list {
+						let (key, folds) = self.begin_event_pair(emit)?;
+						frame.key = Some(key);
+						frame.folds = folds;
+					}
+					
+					match self.lex.peek() {
+						Some(open @ ('{' | '[')) => {
+							let list = open == '[';
+							self.enter(open)?;
+							emit(if list {Event::ListStart} else {Event::DictStart});
+							
+							stack.push(EventFrame {list, root: false, key: None, folds: 0});
+							continue;
+						},
+						
+						Some('"') => emit(Event::StringValue(self.lex.parse_string()?)),
+						Some(_) => emit(Event::StringValue(self.lex.parse_word()?)),
+						p => return Err(self.lex.err(Unexpected(p))),
+					}
+				},
+				
+				_ => {
+					// unwrap should be safe, see above
+					let frame = stack.pop().unwrap();
+					if frame.root {
+						return Ok(());
+					}
+					
+					self.depth -= 1;
+					self.lex.eat(if frame.list {']'} else {'}'})?;
+					emit(if frame.list {Event::ListEnd} else {Event::DictEnd});
+				},
 			}
 			
-			self.eat(open)?;
+			// A value just ended, which closes the dicts its dotted key went through
+			// unwrap should be safe, the stack was just checked to not be empty
+			let frame = stack.last_mut().unwrap();
+			if frame.key.take().is_some() {
+				for _ in 0..mem::take(&mut frame.folds) {
+					emit(Event::DictEnd);
+				}
+			}
 		}
+	}
+	
+	/// Parses the keys and `=` of a pair, reporting every dotted segment as a key of a nested dict.
+	/// Returns the last key along with the number of dicts that were started.
+	fn begin_event_pair<F: FnMut(Event<'s>)>(&mut self, emit: &mut F) -> JsefResult<(Cow<'s, str>, usize)> {
+		let mut key = self.parse_key()?;
+		let mut folds = 0;
+		emit(Event::Key(key.clone()));
+		self.lex.skip_whitespace();
 		
-		self.skip_whitespace();
-		
-		while self.peek().is_some_and(&mut pred) {
-			func(self)?;
-			self.skip_whitespace();
+		while self.lex.try_eat('.') {
+			emit(Event::DictStart);
+			folds += 1;
+			
+			self.lex.skip_whitespace();
+			key = self.parse_key()?;
+			emit(Event::Key(key.clone()));
+			self.lex.skip_whitespace();
 		}
 		
-		if !root {
-			self.depth -= 1;
-			self.eat(close)?;
-		}
+		self.lex.eat('=').map_err(|e| e.in_key(&key))?;
+		self.lex.skip_whitespace();
 		
-		Ok(())
+		Ok((key, folds))
 	}
 	
-	fn parse_value(&mut self) -> JsefResult<JsefValue> {
-		match self.peek() {
-			Some('{') => Ok(JsefValue::Dict(self.parse_dict(false)?)),
-			Some('[') => Ok(JsefValue::List(self.parse_list(false)?)),
-			Some('"') => Ok(JsefValue::String(self.parse_string()?)),
-			Some(_) => Ok(JsefValue::String(self.parse_word()?)),
-			
-			p => Err(self.err(Unexpected(p))),
+	fn parse_value<N: Node<'s>>(&mut self) -> JsefResult<N> {
+		match self.begin_value()? {
+			Begun::Value(value) => Ok(value),
+			Begun::Frame(frame) => Ok(self.parse_nested(frame)?.items.into_node()),
 		}
 	}
 	
-	fn parse_list(&mut self, root: bool) -> JsefResult<JsefList> {
-		let mut list = JsefList::new();
-		self.parse_many(root, '[', ']',
-			|c| c == '"' || c == '[' || c == '{' || is_word_char(c),
-			|this| {
-				let value = this.parse_value()?;
-				list.push(value);
-				Ok(())
-			},
-		)?;
+	/// Parses the items of a root list, which isn't enclosed in brackets.
+	fn parse_list<N: Node<'s>>(&mut self) -> JsefResult<Vec<N>> {
+		let frame = Frame {items: Items::List(Vec::new()), root: true, base: self.path.len()};
 		
-		Ok(list)
+		match self.parse_nested(frame)?.items {
+			Items::List(list) => Ok(list),
+			Items::Dict(..) => unreachable!("the bottom frame is a list"),
+		}
 	}
 	
-	fn parse_dict(&mut self, root: bool) -> JsefResult<JsefDict> {
-		let mut dict = JsefDict::default();
-		self.parse_many(root, '{', '}',
-			|c| c == '"' || is_word_char(c),
-			|this| this.parse_pair(&mut dict),
-		)?;
+	/// Parses the pairs of a root dict, which isn't enclosed in braces.
+	fn parse_dict<N: Node<'s>>(&mut self) -> JsefResult<N::Map> {
+		let frame = Frame {items: Items::<N>::Dict(N::Map::default(), None), root: true, base: self.path.len()};
 		
-		Ok(dict)
+		match self.parse_nested(frame)?.items {
+			Items::Dict(dict, _) => Ok(dict),
+			Items::List(..) => unreachable!("the bottom frame is a dict"),
+		}
+	}
+}
+
+
+/// A list or dict whose items are being parsed.
+struct Frame<'s, N: Node<'s>> {
+	items: Items<'s, N>,
+	/// Whether this is a root list or dict, which isn't enclosed in brackets.
+	root: bool,
+	/// The length of [`Parser::path`] before any of the items.
+	base: usize,
+}
+
+enum Items<'s, N: Node<'s>> {
+	List(Vec<N>),
+	/// Also holds the pair whose value is being parsed, if any.
+	Dict(N::Map, Option<Pair<'s>>),
+}
+
+impl<'s, N: Node<'s>> Items<'s, N> {
+	fn accepts(&self, c: char, opts: &ParseOpts) -> bool {
+		accepts(matches!(self, Self::List(_)), c, opts)
 	}
+	
+	fn close(&self) -> char {
+		match self {
+			Self::List(_) => ']',
+			Self::Dict(..) => '}',
+		}
+	}
+	
+	fn into_node(self) -> N {
+		match self {
+			Self::List(list) => N::from_list(list),
+			Self::Dict(dict, _) => N::from_dict(dict),
+		}
+	}
+}
+
+/// Returns whether `c` starts another item of a list or dict.
+fn accepts(list: bool, c: char, opts: &ParseOpts) -> bool {
+	c == '"' || (list && (c == '[' || c == '{')) || opts.is_word_char(c)
 }
+
+/// A pair whose keys were parsed by [`Parser::begin_pair`].
+struct Pair<'s> {
+	/// The keys leading to the value, including the one it's assigned to.
+	keys: Vec<Cow<'s, str>>,
+	start: usize,
+	/// Where the last key begins.
+	key_start: usize,
+	/// The path checked by [`Parser::check_kind`] so far.
+	path: String,
+}
+
+/// A list or dict whose items are being reported by [`Parser::parse_events`].
+struct EventFrame<'s> {
+	list: bool,
+	/// Whether this is the root dict, which isn't enclosed in braces and is never reported.
+	root: bool,
+	/// The last key of the pair whose value is being parsed, if any.
+	key: Option<Cow<'s, str>>,
+	/// How many dicts the dotted key of that pair went through.
+	folds: usize,
+}
+
+enum Begun<'s, N: Node<'s>> {
+	Value(N),
+	Frame(Frame<'s, N>),
+}
+
@@ -0,0 +1,131 @@
+#![cfg(feature = "serde")]
+
+use std::fmt;
+
+use serde::{
+	Serialize, Serializer, Deserialize, Deserializer,
+	ser::{SerializeSeq, SerializeMap, SerializeStruct},
+	de::{self, Visitor, SeqAccess, MapAccess},
+};
+
+use crate::{JsefValue, JsefList, JsefDict, JsefDiagnostic, float_string};
+
+
+/// Strings, lists and dicts map to their serde counterparts.
+impl Serialize for JsefValue {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::String(string) => serializer.serialize_str(string),
+			
+			Self::List(list) => {
+				let mut seq = serializer.serialize_seq(Some(list.len()))?;
+				for item in list {
+					seq.serialize_element(item)?;
+				}
+				
+				seq.end()
+			},
+			
+			Self::Dict(dict) => {
+				let mut map = serializer.serialize_map(Some(dict.len()))?;
+				for (key, val) in dict {
+					map.serialize_entry(key, val)?;
+				}
+				
+				map.end()
+			},
+		}
+	}
+}
+
+impl Serialize for JsefDiagnostic {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut diagnostic = serializer.serialize_struct("JsefDiagnostic", 6)?;
+		diagnostic.serialize_field("message", &self.message)?;
+		diagnostic.serialize_field("line", &self.line)?;
+		diagnostic.serialize_field("col", &self.col)?;
+		diagnostic.serialize_field("offset", &self.offset)?;
+		diagnostic.serialize_field("file", &self.file)?;
+		diagnostic.serialize_field("code", self.code)?;
+		diagnostic.end()
+	}
+}
+
+/// Numbers, bools and chars are stringified the way JSON writes them,
+/// so `1.5` and `"1.5"` both become the same string and the distinction is lost.
+/// Floats keep their fraction (`1.0` stays `"1.0"`) and nulls become empty strings,
+/// matching the `From<serde_json::Value>` conversion.
+impl<'de> Deserialize<'de> for JsefValue {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_any(ValueVisitor)
+	}
+}
+
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+	type Value = JsefValue;
+	
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "a string, number, bool, null, sequence or map")
+	}
+	
+	fn visit_bool<E: de::Error>(self, value: bool) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value.to_string()))
+	}
+	
+	fn visit_i64<E: de::Error>(self, value: i64) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value.to_string()))
+	}
+	
+	fn visit_u64<E: de::Error>(self, value: u64) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value.to_string()))
+	}
+	
+	fn visit_f64<E: de::Error>(self, value: f64) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(float_string(value)))
+	}
+	
+	fn visit_unit<E: de::Error>(self) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(String::new()))
+	}
+	
+	fn visit_none<E: de::Error>(self) -> Result<JsefValue, E> {
+		self.visit_unit()
+	}
+	
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<JsefValue, D::Error> {
+		deserializer.deserialize_any(self)
+	}
+	
+	fn visit_char<E: de::Error>(self, value: char) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value.to_string()))
+	}
+	
+	fn visit_str<E: de::Error>(self, value: &str) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value.to_owned()))
+	}
+	
+	fn visit_string<E: de::Error>(self, value: String) -> Result<JsefValue, E> {
+		Ok(JsefValue::String(value))
+	}
+	
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsefValue, A::Error> {
+		let mut list = JsefList::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(item) = seq.next_element()? {
+			list.push(item);
+		}
+		
+		Ok(JsefValue::List(list))
+	}
+	
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsefValue, A::Error> {
+		let mut dict = JsefDict::default();
+		while let Some((key, val)) = map.next_entry::<String, JsefValue>()? {
+			dict.insert(key, val);
+		}
+		
+		Ok(JsefValue::Dict(dict))
+	}
+}
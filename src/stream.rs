@@ -0,0 +1,348 @@
+use std::{str, mem, io::{self, Read, BufRead, BufReader, ErrorKind}};
+
+use crate::{
+	JsefValue, JsefList, JsefDict,
+	JsefErrType::*,
+	JsefErr, JsefResult,
+	ParseOpts, Event,
+	parse_value, parse_list, parse_dict,
+	is_word_char, count_line_col,
+	parse::Parser,
+};
+
+
+/// Parses a single [`JsefValue`] from the start of `reader`,
+/// leaving it positioned right after the end of the value.
+/// 
+/// Only looks ahead through [`BufRead::fill_buf`], so nothing after the value is consumed,
+/// not even the char that ends an unquoted word. This allows reading other data
+/// or more values from the same stream afterwards, making values usable as frames of a wire format.
+/// Values don't need any separator between them, except for two unquoted words in a row.
+/// 
+/// Leading whitespace and comments are consumed along with the value and count towards error positions,
+/// and so is a leading UTF-8 BOM. Invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io)
+/// at the position of the first byte that isn't part of a valid char.
+pub fn parse_value_from<R: BufRead + ?Sized>(reader: &mut R) -> JsefResult<JsefValue> {
+	let mut frame = Vec::new();
+	let mut scanner = Scanner::default();
+	
+	skip_bom(reader).map_err(|e| JsefErr::new(Io(e.kind()), 1, 1, 0))?;
+	
+	loop {
+		let buf = match reader.fill_buf() {
+			Ok(buf) => buf,
+			Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+			
+			Err(e) => {
+				let (line, col) = count_line_col(&String::from_utf8_lossy(&frame));
+				return Err(JsefErr::new(Io(e.kind()), line, col, frame.len()));
+			},
+		};
+		
+		if buf.is_empty() {
+			break;
+		}
+		
+		let (len, done) = scanner.scan(buf);
+		frame.extend_from_slice(&buf[..len]);
+		reader.consume(len);
+		
+		if done {
+			break;
+		}
+	}
+	
+	parse_value(utf8((1, 1, 0), &frame)?)
+}
+
+/// Parses a [`JsefList`] from everything left in `reader`, like [`parse_list`].
+/// 
+/// The input is read and parsed in batches of whole items, so only about 64 KiB or the largest item
+/// has to be buffered rather than the whole source. A leading UTF-8 BOM is skipped.
+/// IO errors are reported at the position reached before they occurred,
+/// invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io) at the first byte that isn't part of a valid char.
+pub fn parse_list_from<R: Read>(reader: R) -> JsefResult<JsefList> {
+	let mut list = JsefList::new();
+	
+	FrameReader::new(reader).for_each(Scanner::default, |frame| {
+		list = Parser::new(frame, &ParseOpts::DEFAULT).parse_list_root_into(mem::take(&mut list))?;
+		Ok(())
+	})?;
+	
+	Ok(list)
+}
+
+/// Parses a [`JsefDict`] from everything left in `reader`, like [`parse_dict`].
+/// 
+/// The input is read and parsed in batches of whole pairs, so only about 64 KiB or the largest pair
+/// has to be buffered rather than the whole source. A leading UTF-8 BOM is skipped.
+/// IO errors are reported at the position reached before they occurred,
+/// invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io) at the first byte that isn't part of a valid char.
+pub fn parse_dict_from<R: Read>(reader: R) -> JsefResult<JsefDict> {
+	let mut dict = JsefDict::default();
+	
+	FrameReader::new(reader).for_each(Scanner::pair, |frame| {
+		dict = Parser::new(frame, &ParseOpts::DEFAULT).parse_dict_root_into(mem::take(&mut dict))?;
+		Ok(())
+	})?;
+	
+	Ok(dict)
+}
+
+
+/// Parses a root dict from everything left in `reader` like [`parse_dict_from`],
+/// passing each [`Event`] to `callback` instead of building a [`JsefDict`].
+/// 
+/// Like with [`parse_dict_from`], the input is read in batches of whole pairs,
+/// and events are passed on as soon as a batch was read, so documents of any size can be handled.
+/// Strings are borrowed from the buffered batch when they don't contain escape sequences.
+/// The events follow the source: the root dict is reported too,
+/// dotted keys like `a.b = x` start a nested dict for every segment before the last,
+/// and keys can repeat, in which case [`parse_dict`] would merge or replace their values.
+/// Errors are the same as with [`parse_dict_from`], events before an error have already been passed on.
+pub fn parse_events<R: Read, F: FnMut(Event)>(reader: R, mut callback: F) -> JsefResult {
+	callback(Event::DictStart);
+	
+	FrameReader::new(reader).for_each(Scanner::pair, |frame| {
+		Parser::new(frame, &ParseOpts::DEFAULT).parse_dict_root_events(&mut callback)
+	})?;
+	
+	callback(Event::DictEnd);
+	Ok(())
+}
+
+
+/// Parses a [`JsefValue`] from UTF-8 encoded `bytes`, like [`parse_value`].
+/// 
+/// A leading UTF-8 BOM is skipped, invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io)
+/// at the first byte that isn't part of a valid char, counting from after the BOM like other errors.
+pub fn parse_value_from_bytes(bytes: &[u8]) -> JsefResult<JsefValue> {
+	parse_value(decode(bytes)?)
+}
+
+/// Parses a [`JsefList`] from UTF-8 encoded `bytes`, like [`parse_list`].
+/// 
+/// A leading UTF-8 BOM is skipped, invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io)
+/// at the first byte that isn't part of a valid char, counting from after the BOM like other errors.
+pub fn parse_list_from_bytes(bytes: &[u8]) -> JsefResult<JsefList> {
+	parse_list(decode(bytes)?)
+}
+
+/// Parses a [`JsefDict`] from UTF-8 encoded `bytes`, like [`parse_dict`].
+/// 
+/// A leading UTF-8 BOM is skipped, invalid UTF-8 fails with [`Io`](crate::JsefErrType::Io)
+/// at the first byte that isn't part of a valid char, counting from after the BOM like other errors.
+pub fn parse_dict_from_bytes(bytes: &[u8]) -> JsefResult<JsefDict> {
+	parse_dict(decode(bytes)?)
+}
+
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+fn decode(bytes: &[u8]) -> JsefResult<&str> {
+	let bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+	utf8((1, 1, 0), bytes)
+}
+
+/// Decodes `bytes` starting at `pos`, failing with an error at the first invalid byte.
+fn utf8(pos: (usize, usize, usize), bytes: &[u8]) -> JsefResult<&str> {
+	str::from_utf8(bytes).map_err(|e| {
+		// unwrap should be safe, everything up to valid_up_to was just validated
+		let valid = str::from_utf8(&bytes[..e.valid_up_to()]).unwrap();
+		let (line, col, byte) = advance(pos, valid);
+		JsefErr::new(Io(ErrorKind::InvalidData), line, col, byte)
+	})
+}
+
+/// Frames are read until they add up to this many bytes before being parsed together,
+/// since setting up a parser for every small item costs more than parsing it.
+#[cfg(not(test))]
+const BATCH_LEN: usize = 1 << 16;
+
+/// Small enough for tests to parse frames both on their own and batched up.
+#[cfg(test)]
+const BATCH_LEN: usize = 8;
+
+/// Splits a byte stream into batches of the frames found by a [`Scanner`],
+/// keeping track of the position they start at.
+struct FrameReader<R> {
+	reader: BufReader<R>,
+	frame: Vec<u8>,
+	/// The line, col and byte offset the current batch starts at.
+	pos: (usize, usize, usize),
+}
+
+impl<R: Read> FrameReader<R> {
+	fn new(reader: R) -> Self {
+		Self {
+			reader: BufReader::new(reader),
+			frame: Vec::new(),
+			pos: (1, 1, 0),
+		}
+	}
+	
+	/// Passes every batch of frames until EOF to `parse`, along with the whitespace and comments before them.
+	/// Errors from `parse` are moved to where the batch starts.
+	fn for_each(mut self, scanner: fn() -> Scanner, mut parse: impl FnMut(&str) -> JsefResult) -> JsefResult {
+		skip_bom(&mut self.reader).map_err(|e| JsefErr::new(Io(e.kind()), 1, 1, 0))?;
+		
+		loop {
+			self.frame.clear();
+			
+			let eof = loop {
+				let eof = self.read_frame(scanner())?;
+				if eof || self.frame.len() >= BATCH_LEN {break eof;}
+			};
+			
+			let frame = utf8(self.pos, &self.frame)?;
+			
+			parse(frame).map_err(|e| shift(self.pos, e))?;
+			self.pos = advance(self.pos, frame);
+			
+			if eof {
+				return Ok(());
+			}
+		}
+	}
+	
+	/// Appends the next frame to [`Self::frame`], returning whether EOF was reached before it ended.
+	fn read_frame(&mut self, mut scanner: Scanner) -> JsefResult<bool> {
+		loop {
+			let buf = match self.reader.fill_buf() {
+				Ok(buf) => buf,
+				Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+				
+				Err(e) => {
+					let (line, col, byte) = advance(self.pos, &String::from_utf8_lossy(&self.frame));
+					return Err(JsefErr::new(Io(e.kind()), line, col, byte));
+				},
+			};
+			
+			if buf.is_empty() {
+				return Ok(true);
+			}
+			
+			let (len, done) = scanner.scan(buf);
+			self.frame.extend_from_slice(&buf[..len]);
+			self.reader.consume(len);
+			
+			if done {
+				return Ok(false);
+			}
+		}
+	}
+}
+
+fn skip_bom<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<()> {
+	let buf = loop {
+		match reader.fill_buf() {
+			Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+			buf => break buf?,
+		}
+	};
+	
+	if buf.starts_with(BOM) {
+		reader.consume(BOM.len());
+	}
+	
+	Ok(())
+}
+
+/// Returns the position right after `text` if it starts at `pos`.
+fn advance((line, col, byte): (usize, usize, usize), text: &str) -> (usize, usize, usize) {
+	match count_line_col(text) {
+		(1, end) => (line, col + end - 1, byte + text.len()),
+		(lines, end) => (line + lines - 1, end, byte + text.len()),
+	}
+}
+
+/// Moves an error from a frame parsed on its own to where the frame starts at `pos`.
+fn shift((line, col, byte): (usize, usize, usize), err: JsefErr) -> JsefErr {
+	if err.line == 0 {
+		return err;
+	}
+	
+	JsefErr {
+		col: if err.line == 1 {col + err.col - 1} else {err.col},
+		line: line + err.line - 1,
+		byte: byte + err.byte,
+		..err
+	}
+}
+
+
+/// Finds where the first value of a byte stream ends, without validating it.
+/// 
+/// All structural chars are ASCII, so bytes can be looked at one by one;
+/// anything malformed ends the frame early and is left for the parser to report.
+/// 
+/// When scanning a [`pair`](Self::pair), its keys and `=` come before the value.
+#[derive(Debug, Default)]
+struct Scanner {
+	/// Whether the keys of a pair are being scanned.
+	key: bool,
+	depth: usize,
+	word: bool,
+	string: bool,
+	escape: bool,
+	comment: bool,
+}
+
+impl Scanner {
+	fn pair() -> Self {
+		Self {key: true, ..Self::default()}
+	}
+	
+	/// Returns how many bytes of `bytes` belong to the value and whether it ended within them.
+	fn scan(&mut self, bytes: &[u8]) -> (usize, bool) {
+		for (i, &b) in bytes.iter().enumerate() {
+			if self.comment {
+				self.comment = b != b'\n';
+				continue;
+			}
+			
+			if self.string {
+				if self.escape {
+					self.escape = false;
+				} else if b == b'\\' {
+					self.escape = true;
+				} else if b == b'"' {
+					self.string = false;
+					if self.depth == 0 && !self.key {return (i + 1, true);}
+				}
+				
+				continue;
+			}
+			
+			if self.word {
+				if is_word_byte(b) {continue;}
+				if !self.key {return (i, true);}
+				self.word = false;
+			}
+			
+			match b {
+				b'#' => self.comment = true,
+				b'"' => self.string = true,
+				b'=' if self.key => self.key = false,
+				b'.' if self.key => {},
+				b'[' | b'{' if !self.key => self.depth += 1,
+				
+				b']' | b'}' if !self.key => {
+					self.depth = self.depth.saturating_sub(1);
+					if self.depth == 0 {return (i + 1, true);}
+				},
+				
+				b if b.is_ascii_whitespace() || self.depth > 0 => {},
+				b if is_word_byte(b) => self.word = true,
+				_ => return (i + 1, true),
+			}
+		}
+		
+		(bytes.len(), false)
+	}
+}
+
+
+fn is_word_byte(b: u8) -> bool {
+	!b.is_ascii() || is_word_char(b as char)
+}
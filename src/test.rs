@@ -4,13 +4,15 @@
 use super::*;
 
 
-const OPTS: [ComposeOpts; 4] = [
+const OPTS: [ComposeOpts; 5] = [
 	ComposeOpts::SIMPLE,
 	ComposeOpts::COMPACT,
 	ComposeOpts::PRETTY,
 	ComposeOpts::PRETTY
 		.no_indent()
 		.prelude("test\nprelude"),
+	ComposeOpts::COMPACT
+		.prelude("test\nprelude"),
 ];
 
 
@@ -19,18 +21,23 @@ fn errors() {
 	use JsefErrType::*;
 	
 	const ERRORS: [(&str, JsefErr); 6] = [
-		("0 1 2]",         JsefErr::new(NotEof('1'),              1, 3)),
-		("[0 1 2}",        JsefErr::new(Mismatch(']', Some('}')), 1, 7)),
-		("\"value",        JsefErr::new(Mismatch('"', None),      1, 7)),
-		("{a=0 b.=1 c=2}", JsefErr::new(Unexpected(Some('=')),    1, 8)),
-		("{a=0 .b=1 c=2}", JsefErr::new(Mismatch('}', Some('.')), 1, 6)),
-		("[0 [1 [2]]]",    JsefErr::new(MaxDepth,                 1, 7)),
+		("0 1 2]",         JsefErr::new(NotEof('1'),              1, 3, 2)),
+		("[0 1 2}",        JsefErr::new(Mismatch(']', Some('}')), 1, 7, 6)),
+		("\"value",        JsefErr::new(Mismatch('"', None),      1, 7, 6)),
+		("{a=0 b.=1 c=2}", JsefErr::new(Unexpected(Some('=')),    1, 8, 7)),
+		("{a=0 .b=1 c=2}", JsefErr::new(Mismatch('}', Some('.')), 1, 6, 5)),
+		("[0 [1 [2]]]",    JsefErr::new(MaxDepth,                 1, 7, 6)),
 	];
 	
 	for (src, err) in ERRORS {
 		let result = parse_value(src).unwrap_err();
 		assert_eq!(result, err);
 	}
+	
+	let diagnostic = ERRORS[1].1.to_diagnostic();
+	assert_eq!(diagnostic.message, "expected ']', got '}'");
+	assert_eq!(diagnostic.code, "mismatch");
+	assert_eq!((diagnostic.line, diagnostic.col), (1, 7));
 }
 
 
@@ -82,11 +89,12 @@ fn parse() {
 
 #[test]
 fn compose() {
-	const TARGETS: [&str; 4] = [
+	const TARGETS: [&str; 5] = [
 		r#"[["0"] "1" "2"] {"path"={"to"="a value"}} "other""#,
 		r#"[[0] 1 2] {path.to="a value"} other"#,
 		"[\n\t[\n\t\t0\n\t]\n\t1\n\t2\n]\n{\n\tpath.to = \"a value\"\n}\nother",
 		"# test\n# prelude\n[ [ 0 ] 1 2 ] { path.to = \"a value\" } other",
+		"# test\n# prelude\n[[0] 1 2] {path.to=\"a value\"} other",
 	];
 	
 	let mut root = JsefList::new();
@@ -166,4 +174,1685 @@ fn stresstest() {
 		let parsed = parse_dict(&composed).unwrap();
 		assert_eq!(parsed, root);
 	}
+	
+	let tokens: Vec<_> = Lexer::new(SOURCE, &ParseOpts::DEFAULT).collect::<JsefResult<_>>().unwrap();
+	let kinds: Vec<_> = tokens.iter().take(8).map(|t| t.kind.clone()).collect();
+	assert_eq!(kinds, [
+		TokenKind::Word("key".into()), TokenKind::Equals, TokenKind::Word("value".into()),
+		TokenKind::Word("list".into()), TokenKind::Equals, TokenKind::OpenBracket,
+		TokenKind::Word("0".into()), TokenKind::Word("1".into()),
+	]);
+	
+	assert_eq!(tokens.len(), 48);
+	for token in &tokens {
+		let text = &SOURCE[token.bytes.clone()];
+		match &token.kind {
+			TokenKind::Word(word) => assert_eq!(text, word),
+			TokenKind::Comment(comment) => assert_eq!(text, format!("#{comment}")),
+			TokenKind::QuotedString(_) => assert!(text.len() >= 2 && text.starts_with('"') && text.ends_with('"')),
+			_ => assert_eq!(text.len(), 1),
+		}
+		
+		let (line, col) = count_line_col(&SOURCE[..token.bytes.end]);
+		assert_eq!((token.end_line, token.end_col), (line, col));
+	}
+	
+	let root = JsefValue::Dict(root);
+	let mut paths: Vec<_> = root.iter_tree().map(|(path, _)| path).collect();
+	paths.sort();
+	
+	let expected = ["", "#", "0", "dict", "dict.a", "dict.a.oops", "dict.b", "key", "list", "list.0", "list.1", "list.2", "list.3"];
+	assert_eq!(paths, expected);
+	
+	let list: Vec<_> = root.iter_tree().filter(|(path, _)| path.starts_with("list")).collect();
+	assert_eq!(list[0], ("list".to_owned(), &root["list"]));
+	assert_eq!(list[1..].iter().map(|(_, val)| val.as_string().unwrap().as_str()).collect::<String>(), "0123");
+}
+
+
+#[test]
+fn align_equals() {
+	let mut root = JsefDict::default();
+	let mut folded = JsefDict::default();
+	
+	folded.insert("d".to_owned(), JsefValue::string_from("3"));
+	root.insert("a".to_owned(), JsefValue::string_from("1"));
+	root.insert("b c".to_owned(), JsefValue::string_from("2"));
+	root.insert("long".to_owned(), JsefValue::Dict(folded));
+	
+	let opts = ComposeOpts::PRETTY.align_equals(true);
+	let composed = compose_dict(&root, &opts).unwrap();
+	let mut lines: Vec<_> = composed.lines().collect();
+	lines.sort();
+	
+	assert_eq!(lines, ["\"b c\" = 2", "a     = 1", "long.d = 3"]);
+	assert_eq!(parse_dict(&composed).unwrap(), root);
+}
+
+
+#[test]
+fn quoting() {
+	assert!(needs_quotes(""));
+	assert!(needs_quotes("a b"));
+	assert!(needs_quotes("a=b"));
+	assert!(!needs_quotes("value"));
+	assert!(!needs_quotes("back\\slash"));
+	
+	let mut root = JsefDict::default();
+	root.insert("".to_owned(), JsefValue::new_string());
+	root.insert("back\\slash".to_owned(), JsefValue::string_from("\\"));
+	
+	for opts in OPTS.iter() {
+		let composed = compose_dict(&root, opts).unwrap();
+		let parsed = parse_dict(&composed).unwrap();
+		assert_eq!(parsed, root);
+	}
+}
+
+
+#[test]
+fn lint() {
+	const SOURCE: &str = r#"port=8o80 other=8080 list=["1.5" -1e3 2x] version.patch="1.2.3" name=x86"#;
+	
+	let parsed = JsefValue::Dict(parse_dict(SOURCE).unwrap());
+	let mut found = lint_scalars(&parsed);
+	found.sort_by(|a, b| a.path.cmp(&b.path));
+	
+	let found: Vec<_> = found
+		.iter()
+		.map(|s| (s.path.as_str(), s.value.as_str()))
+		.collect();
+	
+	assert_eq!(found, [("list.2", "2x"), ("port", "8o80"), ("version.patch", "1.2.3")]);
+}
+
+
+#[test]
+fn list_separator() {
+	let list = JsefList::from([
+		JsefValue::string_from("a"),
+		JsefValue::string_from("b,c"),
+		JsefValue::list_from([
+			JsefValue::string_from("x"),
+			JsefValue::string_from("y"),
+		]),
+	]);
+	
+	let opts = ComposeOpts::COMPACT.list_separator(", ");
+	let composed = compose_list(&list, &opts).unwrap();
+	assert_eq!(composed, r#"a, "b,c", [x, y]"#);
+	
+	let parse_opts = ParseOpts::DEFAULT.commas_ok(true);
+	let parsed = parse_list_with(&composed, &parse_opts).unwrap();
+	assert_eq!(parsed, list);
+	
+	let parsed = parse_list(&composed).unwrap();
+	assert_ne!(parsed, list);
+	
+	let opts = ComposeOpts::COMPACT.list_separator(" ,\n");
+	assert_eq!(parse_list_with(&compose_list(&list, &opts).unwrap(), &parse_opts).unwrap(), list);
+	
+	for sep in ["", ";", " | ", "\u{a0}"] {
+		let err = JsefErr::new(JsefErrType::InvalidSeparator(sep.to_owned()), 0, 0, 0);
+		let opts = ComposeOpts::COMPACT.list_separator(sep);
+		assert_eq!(compose_list(&list, &opts), Err(err.clone()));
+		assert_eq!(compose_list_to(&list, &opts, Vec::new()), Err(err));
+	}
+	
+	let opts = ComposeOpts::PRETTY.list_separator(";");
+	assert!(compose_list(&list, &opts).is_err());
+}
+
+
+#[test]
+fn commas() {
+	const SOURCES: [&str; 3] = [
+		"a=[x y] b={c=1 d=2}",
+		",a=[x, y], b={c=1,, d=2,},",
+		"a = [,x ,y,] , b = { , c = 1 , d = 2 }",
+	];
+	
+	let opts = ParseOpts::DEFAULT.commas_ok(true);
+	let expected = parse_dict(SOURCES[0]).unwrap();
+	
+	for src in SOURCES {
+		let parsed = parse_dict_with(src, &opts).unwrap();
+		assert_eq!(parsed, expected);
+	}
+	
+	let parsed = parse_dict("a=x,y").unwrap();
+	assert_eq!(parsed["a"], "x,y");
+	assert!(parse_dict_with("a=x,y", &opts).is_err());
+	
+	let composed = compose_dict(&expected, &ComposeOpts::COMPACT.list_separator(", ")).unwrap();
+	let parsed = parse_dict_with(&composed, &opts).unwrap();
+	assert_eq!(parsed, expected);
+}
+
+
+#[test]
+fn into_flat() {
+	const SOURCE: &str = "a.b=1 a.c=[x {}] d=[] e=2";
+	
+	let parsed = JsefValue::Dict(parse_dict(SOURCE).unwrap());
+	let mut flat: Vec<_> = parsed.into_flat().collect();
+	flat.sort_by(|a, b| a.0.cmp(&b.0));
+	
+	assert_eq!(flat, [
+		("a.b".to_owned(), JsefValue::string_from("1")),
+		("a.c.0".to_owned(), JsefValue::string_from("x")),
+		("a.c.1".to_owned(), JsefValue::new_dict()),
+		("d".to_owned(), JsefValue::new_list()),
+		("e".to_owned(), JsefValue::string_from("2")),
+	]);
+	
+	let flat: Vec<_> = JsefValue::string_from("x").into_flat().collect();
+	assert_eq!(flat, [(String::new(), JsefValue::string_from("x"))]);
+}
+
+
+#[test]
+fn path_entry() {
+	let mut value = JsefValue::Dict(parse_dict("a=1 b.c=2").unwrap());
+	*value.path_entry("a.x") = JsefValue::string_from("3");
+	*value.path_entry("b.d") = JsefValue::string_from("4");
+	
+	let expected = parse_dict("a.x=3 b.c=2 b.d=4").unwrap();
+	assert_eq!(value, expected);
+	
+	let mut value = JsefValue::string_from("scalar");
+	assert_eq!(*value.path_entry("a"), JsefValue::new_dict());
+	
+	let err = value.try_path_entry("a.b.c").unwrap_err();
+	assert_eq!(err.err, JsefErrType::MaxDepth);
+	
+	let mut value = JsefValue::new_dict();
+	*value.path_entry_sep("a.b/c", "/") = JsefValue::string_from("x");
+	assert_eq!(value, parse_dict(r#""a.b".c=x"#).unwrap());
+}
+
+
+#[test]
+fn padded_strings() {
+	let parsed = parse_value(r#"{a=" padded " b="   "}"#).unwrap();
+	assert_eq!(parsed.as_dict().unwrap()["a"], " padded ");
+	assert_eq!(parsed.as_dict().unwrap()["b"], "   ");
+	
+	for opts in OPTS.iter() {
+		let composed = compose_value(&parsed, opts).unwrap();
+		assert_eq!(parse_value(&composed).unwrap(), parsed);
+		
+		let spaces = JsefValue::string_from("  ");
+		let composed = compose_value(&spaces, opts).unwrap();
+		assert!(composed.ends_with("\"  \""));
+		assert_eq!(parse_value(&composed).unwrap(), spaces);
+	}
+}
+
+
+#[test]
+fn raw_strings() {
+	const SOURCE: &str = r#""C:\new\dir \"quoted\"""#;
+	
+	let opts = ParseOpts::DEFAULT.raw_strings(true);
+	let parsed = parse_value_with(SOURCE, &opts).unwrap();
+	assert_eq!(parsed, r#"C:\new\dir "quoted""#);
+	
+	let composed = compose_value(&parsed, &ComposeOpts::SIMPLE.raw_strings(true)).unwrap();
+	assert_eq!(composed, SOURCE);
+	assert_eq!(parse_value_with(&composed, &opts).unwrap(), parsed);
+	
+	let opts = ComposeOpts::SIMPLE.raw_strings(true);
+	let err = JsefErr::new(JsefErrType::TrailingBackslash, 1, 1, 0);
+	assert_eq!(compose_value(&JsefValue::from("a\\"), &opts), Err(err));
+	
+	let dict = parse_dict(r#"a="x\\\\""#).unwrap();
+	let err = JsefErr::new(JsefErrType::TrailingBackslash, 1, 5, 4);
+	assert_eq!(compose_dict(&dict, &opts), Err(err));
+}
+
+
+#[test]
+fn parse_cow() {
+	use std::borrow::Cow;
+	
+	const SOURCE: &str = r#"{plain=value quoted="a value" escaped="a\nvalue" list=[x]}"#;
+	
+	let parsed = parse_value_cow(SOURCE).unwrap();
+	let dict = parsed.as_dict().unwrap();
+	
+	assert!(matches!(dict["plain"], JsefValueCow::String(Cow::Borrowed("value"))));
+	assert!(matches!(dict["quoted"], JsefValueCow::String(Cow::Borrowed("a value"))));
+	assert!(matches!(&dict["escaped"], JsefValueCow::String(Cow::Owned(s)) if s == "a\nvalue"));
+	
+	let keys: Vec<_> = dict.keys().filter(|key| matches!(key, Cow::Borrowed(_))).collect();
+	assert_eq!(keys.len(), 4);
+	
+	let escaped = parse_value_ref(r#"{"k\u{65}y"=v}"#).unwrap();
+	assert!(matches!(escaped.as_dict().unwrap().keys().next(), Some(Cow::Owned(key)) if key == "key"));
+	
+	assert_eq!(parsed.to_owned(), parse_value(SOURCE).unwrap());
+	assert_eq!(parsed.into_owned(), parse_value(SOURCE).unwrap());
+	
+	// Strings without escapes are scanned byte by byte and borrowed whole, whatever chars they hold
+	let long = "wörter ✓ \u{85}\t".repeat(1000);
+	let source = format!("\"{long}\"");
+	let parsed = parse_value_cow(&source).unwrap();
+	assert!(matches!(parsed, JsefValueCow::String(Cow::Borrowed(s)) if s == long));
+	
+	let source = format!("\"{long}\\\"{long}\"");
+	let parsed = parse_value_cow(&source).unwrap();
+	assert!(matches!(parsed, JsefValueCow::String(Cow::Owned(s)) if s == format!("{long}\"{long}")));
+	
+	let opts = ParseOpts::DEFAULT.raw_controls(RawControls::RejectC0);
+	assert_eq!(parse_value_with("\"ä\u{85}\tb\"", &opts), Err(JsefErr::new(JsefErrType::RawControl('\t'), 1, 4, 5)));
+}
+
+
+#[test]
+fn compose_sized() {
+	let value = parse_value(r#"{a=[x "y z"] b.c="multi\nline" d={}}"#).unwrap();
+	
+	for opts in OPTS.iter() {
+		let len = composed_len(&value, opts).unwrap();
+		let composed = compose_value_sized(&value, opts).unwrap();
+		
+		assert_eq!(len, composed.len());
+		assert_eq!(composed.capacity(), len);
+		assert_eq!(parse_value(&composed).unwrap(), value);
+	}
+}
+
+
+#[test]
+fn parse_info() {
+	const SOURCE: &str = "{a=[1 2] b.c=3}";
+	
+	let (value, info) = parse_value_with_info(SOURCE, &ParseOpts::DEFAULT).unwrap();
+	assert_eq!(value, parse_value(SOURCE).unwrap());
+	assert_eq!(info, ParseInfo {peak_depth: 2, bytes: SOURCE.len(), items: 5});
+}
+
+
+#[test]
+fn empty_input() {
+	use JsefErrType::*;
+	
+	const EMPTY: [&str; 3] = ["", "  \n\t", "# all\n  # commented out\n"];
+	
+	for src in EMPTY {
+		assert_eq!(parse_dict(src).unwrap(), JsefDict::default());
+		assert_eq!(parse_list(src).unwrap(), JsefList::new());
+		assert_eq!(parse_value_or_empty(src).unwrap(), JsefValue::new_dict());
+		assert_eq!(parse_value(src).unwrap_err().err, Unexpected(None));
+	}
+	
+	assert_eq!(parse_value_or_empty("# x\n[a]").unwrap(), parse_value("[a]").unwrap());
+}
+
+
+#[test]
+fn fold_path() {
+	let value = parse_value(r#"{a."b c".d=x}"#).unwrap();
+	
+	let (path, leaf) = super::fold_path(&value);
+	assert_eq!(path, ["a", "b c", "d"]);
+	assert_eq!(leaf, "x");
+	
+	let composed = compose_value(&value, &ComposeOpts::COMPACT).unwrap();
+	assert_eq!(composed, r#"{a."b c".d=x}"#);
+	assert_eq!(parse_value(&composed).unwrap(), value);
+}
+
+
+#[test]
+fn control_chars() {
+	const VALUES: [&str; 3] = ["form\x0Cfeed", "vertical\x0Btab", "nul\0"];
+	
+	for val in VALUES {
+		assert!(needs_quotes(val));
+		assert!(parse_dict(&format!("a={val}")).is_err());
+		
+		let value = JsefValue::string_from(val);
+		for opts in OPTS.iter() {
+			let composed = compose_value(&value, opts).unwrap();
+			assert_eq!(parse_value(&composed).unwrap(), value);
+		}
+	}
+}
+
+
+#[cfg(feature = "intern")]
+#[test]
+fn intern_values() {
+	use std::sync::Arc;
+	
+	let value = parse_value("[enabled enabled {enabled=enabled}]").unwrap();
+	let shared = super::intern_values(value.clone());
+	
+	let SharedValue::List(list) = &shared else {panic!()};
+	let (SharedValue::String(a), SharedValue::String(b)) = (&list[0], &list[1]) else {panic!()};
+	assert!(Arc::ptr_eq(a, b));
+	
+	assert_eq!(shared.to_value(), value);
+}
+
+
+#[test]
+fn from_iters() {
+	let list = JsefValue::from_values(["a", "b"]);
+	assert_eq!(list, parse_value("[a b]").unwrap());
+	assert!(list.as_list().unwrap().capacity() >= 2);
+	
+	let dict = JsefValue::from_pairs([("a", list), ("b", "c".into())]);
+	assert_eq!(dict, parse_value("{a=[a b] b=c}").unwrap());
+	
+	assert_eq!(JsefValue::dict_with_capacity(8), JsefValue::new_dict());
+}
+
+
+#[test]
+fn root_scalars() {
+	const SCALARS: [&str; 7] = ["", "with spaces", "{", "[]", "#", "a.b", "\"quoted\""];
+	
+	for scalar in SCALARS {
+		let value = JsefValue::string_from(scalar);
+		
+		for opts in OPTS.iter() {
+			let composed = compose_value(&value, opts).unwrap();
+			assert_eq!(parse_value(&composed).unwrap(), value);
+		}
+	}
+}
+
+
+#[test]
+fn includes() {
+	use std::io::{self, Cursor, ErrorKind};
+	
+	fn resolver(path: &str) -> io::Result<Cursor<&'static str>> {
+		let source = match path {
+			"main" => "#include \"base\"\nname=main\ndict.b=2",
+			"base" => "name=base\ndict={a=1 b=1}\n  #include \"common\"",
+			"common" => "common=yes",
+			"cycle" => "#include \"cycle\"",
+			"missing" => "\n#include \"nothing\"",
+			"nested" => "dict={\n\t#include \"nothing\"\n}\nname=x #include \"nothing\"\n#include \"common\"",
+			"broken" => "a=1\n#include \"typo\"",
+			"typo" => "#include \"common\"\nb=[x",
+			"deep" => "#include \"broken\"",
+			_ => return Err(ErrorKind::NotFound.into()),
+		};
+		
+		Ok(Cursor::new(source))
+	}
+	
+	let loaded = load_with_includes("main", resolver).unwrap();
+	assert_eq!(loaded, parse_dict("name=main dict.a=1 dict.b=2 common=yes").unwrap());
+	
+	let err = load_with_includes("cycle", resolver).unwrap_err();
+	assert_eq!(err, JsefErr::new(JsefErrType::IncludeCycle("cycle".to_owned()), 1, 1, 0));
+	
+	let err = load_with_includes("missing", resolver).unwrap_err();
+	assert_eq!(err, JsefErr::new(JsefErrType::Io(ErrorKind::NotFound), 2, 1, 1));
+	
+	let loaded = load_with_includes("nested", resolver).unwrap();
+	assert_eq!(loaded, parse_dict("dict={} name=x common=yes").unwrap());
+	
+	let err = JsefErr::new(JsefErrType::Mismatch(']', None), 2, 5, 22).in_key("b").in_file("typo");
+	assert_eq!(load_with_includes("broken", resolver), Err(err.clone()));
+	assert_eq!(load_with_includes("deep", resolver), Err(err.clone()));
+	assert_eq!(err.to_string(), "JSeF error in \"typo\" at line 2, col 5: while parsing key \"b\": expected ']', got EOF");
+}
+
+
+#[test]
+fn compose_sink() {
+	struct Brackets(String);
+	
+	impl ComposeSink for Brackets {
+		fn write_str(&mut self, text: &str) {
+			self.0.push_str(text);
+		}
+		
+		fn key(&mut self, text: &str) {
+			self.0 += &format!("<k>{text}</k>");
+		}
+		
+		fn scalar(&mut self, text: &str) {
+			self.0 += &format!("<s>{text}</s>");
+		}
+	}
+	
+	let value = parse_value("{a.b=\"x y\"}").unwrap();
+	let mut sink = Brackets(String::new());
+	compose_value_sink(&value, &ComposeOpts::COMPACT, &mut sink).unwrap();
+	assert_eq!(sink.0, "{<k>a</k>.<k>b</k>=<s>\"x y\"</s>}");
+	
+	for opts in OPTS {
+		let mut plain = String::new();
+		compose_value_sink(&value, &opts, &mut plain).unwrap();
+		assert_eq!(plain, compose_value(&value, &opts).unwrap());
+	}
+}
+
+
+#[test]
+fn strict_types() {
+	use JsefKind::*;
+	
+	let opts = ParseOpts::DEFAULT.strict_types(true);
+	let conflict = |path: &str, expected, found, line, col, byte| {
+		JsefErr::new(JsefErrType::TypeConflict {path: path.to_owned(), expected, found}, line, col, byte)
+	};
+	
+	assert_eq!(parse_dict_with("a.b=1 a=2", &opts), Err(conflict("a", Dict, String, 1, 7, 6)));
+	assert_eq!(parse_dict_with("a=1\na.b=2", &opts), Err(conflict("a", String, Dict, 2, 1, 4)));
+	assert_eq!(parse_dict_with("a.b=1 a.b.c=2", &opts), Err(conflict("a.b", String, Dict, 1, 7, 6)));
+	assert_eq!(parse_dict_with("x={a=[]} x.a=y", &opts), Err(conflict("x.a", List, String, 1, 10, 9)));
+	
+	let dict = parse_dict_with("a.b=1 a.c=2 a={d=3} b=1 b=2", &opts).unwrap();
+	assert_eq!(dict, parse_dict("a.d=3 b=2").unwrap());
+	assert!(parse_dict("a.b=1 a=2 a.b=3").is_ok());
+}
+
+
+#[test]
+fn inline_threshold() {
+	let opts = ComposeOpts::PRETTY.dense(true).inline_threshold(2);
+	
+	let value = parse_value("[[a b] [a b c] {x=y}]").unwrap();
+	let composed = compose_value(&value, &opts).unwrap();
+	assert_eq!(composed, "[\n\t[a b]\n\t[\n\t\ta\n\t\tb\n\t\tc\n\t]\n\t{x=y}\n]");
+	
+	// Everything inside an inlined collection stays on the same line
+	let dict = parse_dict("x=[[a b c]]").unwrap();
+	let composed = compose_dict(&dict, &opts.clone().inline_threshold(1)).unwrap();
+	assert_eq!(composed, "x=[[a b c]]");
+	
+	let composed = compose_value(&value, &opts.dense(false)).unwrap();
+	assert_eq!(parse_value(&composed).unwrap(), value);
+}
+
+
+#[test]
+fn lexer() {
+	use TokenKind::*;
+	
+	let source = "# note\nkey.\"a b\" = [x\n  {y=\"\\n\"}]";
+	let tokens: Vec<_> = Lexer::new(source, &ParseOpts::DEFAULT)
+		.map(|token| token.map(|t| (t.kind, t.line, t.col)))
+		.collect::<JsefResult<_>>()
+		.unwrap();
+	
+	assert_eq!(tokens, [
+		(Comment(" note"), 1, 1),
+		(Word("key".into()), 2, 1),
+		(Dot, 2, 4),
+		(QuotedString("a b".into()), 2, 5),
+		(Equals, 2, 11),
+		(OpenBracket, 2, 13),
+		(Word("x".into()), 2, 14),
+		(OpenBrace, 3, 3),
+		(Word("y".into()), 3, 4),
+		(Equals, 3, 5),
+		(QuotedString("\n".into()), 3, 6),
+		(CloseBrace, 3, 10),
+		(CloseBracket, 3, 11),
+	]);
+	
+	let mut lexer = Lexer::new("a \"b", &ParseOpts::DEFAULT);
+	assert!(lexer.next().unwrap().is_ok());
+	assert_eq!(lexer.next(), Some(Err(JsefErr::new(JsefErrType::Mismatch('"', None), 1, 5, 4))));
+	assert_eq!(lexer.next(), None);
+	
+	let token = Lexer::new("a\n  \"b\nc\" d", &ParseOpts::DEFAULT).nth(1).unwrap().unwrap();
+	assert_eq!((token.line, token.col, token.end_line, token.end_col, token.bytes), (2, 3, 3, 3, 4..9));
+	
+	let commas = ParseOpts::DEFAULT.commas_ok(true);
+	assert_eq!(Lexer::new(",a,,b,", &commas).count(), 2);
+}
+
+
+#[test]
+fn max_key_len() {
+	let opts = ParseOpts::DEFAULT.max_key_len(4);
+	let long = "x".repeat(KEY_PREFIX_LEN + 4);
+	let prefix = "x".repeat(KEY_PREFIX_LEN);
+	
+	assert!(parse_dict_with("abcd.\"ab\\n\"=abcdefgh", &opts).is_ok());
+	assert_eq!(parse_dict_with("a=1 abcde=2", &opts), Err(JsefErr::new(JsefErrType::KeyTooLong("abcde".to_owned()), 1, 5, 4)));
+	assert_eq!(parse_dict_with(&format!("a.{long}=1"), &opts), Err(JsefErr::new(JsefErrType::KeyTooLong(prefix), 1, 3, 2)));
+	assert!(parse_dict(&format!("{long}=1")).is_ok());
+}
+
+
+#[test]
+fn assert_jsef_eq() {
+	let left = parse_value("{a = [x y] b.c = d}").unwrap();
+	let right = parse_value("{a = [x z] b.c = d}").unwrap();
+	
+	assert_jsef_eq!(left, left.clone());
+	
+	let panic = std::panic::catch_unwind(|| assert_jsef_eq!(left, right, "case {}", 1)).unwrap_err();
+	let msg = panic.downcast_ref::<String>().unwrap();
+	
+	assert!(msg.starts_with("JSeF values differ at a.1: case 1\n"));
+	assert!(msg.contains("\n- \t\ty\n+ \t\tz\n"));
+}
+
+#[test]
+fn assert_jsef_eq_large() {
+	let left = JsefValue::List((0..2000).map(|i| JsefValue::from(i.to_string())).collect());
+	let right = JsefValue::List((0..2000).rev().map(|i| JsefValue::from(i.to_string())).collect());
+	
+	let panic = std::panic::catch_unwind(|| assert_jsef_eq!(left, right)).unwrap_err();
+	let msg = panic.downcast_ref::<String>().unwrap();
+	
+	// Too many lines to align, so every element is removed and then added
+	assert!(msg.contains("\n  [\n- \t0\n- \t1\n"));
+	assert!(msg.contains("\n- \t1999\n+ \t1999\n"));
+	assert!(msg.ends_with("\n+ \t0\n  ]\n"));
+}
+
+
+#[test]
+fn parse_lines() {
+	let source = "a\n\n  # comment\n{x=y}\r\n[\"a\"\n[b c]";
+	let values: Vec<_> = super::parse_lines(source.as_bytes()).collect();
+	
+	assert_eq!(values, [
+		Ok(JsefValue::from("a")),
+		parse_value("{x=y}"),
+		Err(JsefErr::new(JsefErrType::Mismatch(']', None), 5, 5, 26)),
+		parse_value("[b c]"),
+	]);
+}
+
+
+#[test]
+fn prelude() {
+	let value = parse_value("{a=[b]}").unwrap();
+	let opts = ComposeOpts::PRETTY.prelude("first\nsecond");
+	
+	let composed = compose_value(&value, &opts).unwrap();
+	assert_eq!(composed, "# first\n# second\n{\n\ta = [\n\t\tb\n\t]\n}");
+	
+	let composed = compose_dict(value.as_dict().unwrap(), &opts.prelude_marker("#")).unwrap();
+	assert_eq!(composed, "#first\n#second\na = [\n\tb\n]");
+	assert_eq!(parse_dict(&composed), parse_dict("a=[b]"));
+}
+
+
+#[test]
+fn project() {
+	let value = parse_value("{a.b=1 a.c=2 d={e=3 f=4} g=[5]}").unwrap();
+	
+	let projected = value.project(&["a.b", "d", "g", "g.0", "missing", "a.b.c"]);
+	assert_eq!(projected, parse_value("{a.b=1 d={e=3 f=4} g=[5]}").unwrap());
+	
+	assert_eq!(value.project(&[]), JsefValue::new_dict());
+	assert_eq!(JsefValue::from("x").project(&["x"]), JsefValue::new_dict());
+}
+
+
+#[test]
+fn compose_errors() {
+	let deep = JsefValue::list_from([JsefValue::list_from([JsefValue::new_list()])]);
+	let dict = JsefValue::from_pairs([("key", deep.clone())]).take_dict().unwrap();
+	
+	let err = compose_value(&deep, &ComposeOpts::COMPACT).unwrap_err();
+	assert_eq!(err, JsefErr::new(JsefErrType::MaxDepth, 1, 3, 2));
+	
+	let opts = ComposeOpts::PRETTY.prelude("a\nb");
+	let err = compose_dict(&dict, &opts).unwrap_err();
+	assert_eq!(err, JsefErr::new(JsefErrType::MaxDepth, 5, 3, 21));
+	assert_eq!(composed_len(&deep, &opts), Err(JsefErr::new(JsefErrType::MaxDepth, 5, 3, 15)));
+}
+
+
+#[test]
+fn preset_names() {
+	let value = parse_value("{a=[b c]}").unwrap();
+	
+	for (name, opts) in [("pretty", ComposeOpts::PRETTY), ("Compact", ComposeOpts::COMPACT), ("SIMPLE", ComposeOpts::SIMPLE)] {
+		let parsed: ComposeOpts = name.parse().unwrap();
+		assert_eq!(compose_value(&value, &parsed), compose_value(&value, &opts));
+	}
+	
+	let err = "fancy".parse::<ComposeOpts>().unwrap_err();
+	assert_eq!(err.to_string(), "unknown compose preset \"fancy\", expected one of: pretty, compact, simple");
+}
+
+
+#[test]
+fn multibyte_escapes() {
+	// No multibyte char is escaped yet, but escapes right next to them must not split them
+	let value = JsefValue::from("é\n€\"𝄞\\ü");
+	let composed = compose_value(&value, &ComposeOpts::COMPACT).unwrap();
+	
+	assert_eq!(composed, "\"é\\n€\\\"𝄞\\\\ü\"");
+	assert_eq!(parse_value(&composed).unwrap(), value);
+}
+
+
+#[test]
+fn raw_controls() {
+	let nul = ParseOpts::DEFAULT.raw_controls(RawControls::RejectNul);
+	let c0 = ParseOpts::DEFAULT.raw_controls(RawControls::RejectC0);
+	
+	assert_eq!(parse_value("\"a\0b\"").unwrap(), "a\0b");
+	assert_eq!(parse_value_with("\"a\\0\tb\"", &nul).unwrap(), "a\0\tb");
+	assert_eq!(parse_value_with("\"a\\n\0b\"", &nul), Err(JsefErr::new(JsefErrType::RawControl('\0'), 1, 5, 4)));
+	assert_eq!(parse_value_with("[\"a\"\n\"\tb\"]", &c0), Err(JsefErr::new(JsefErrType::RawControl('\t'), 2, 2, 6)));
+	
+	let err = JsefErrType::RawControl('\u{1b}');
+	assert_eq!(err.to_string(), "unescaped control char U+001B in string");
+}
+
+
+#[test]
+fn take_helpers() {
+	let string = JsefValue::from("abc");
+	let list = JsefValue::new_list();
+	
+	assert_eq!(string.clone().take_string_or("x".to_owned()), "abc");
+	assert_eq!(list.clone().take_string_or("x".to_owned()), "x");
+	assert_eq!(list.clone().unwrap_list(), JsefList::new());
+	assert_eq!(string.clone().map_string(|s| s.to_uppercase()), "ABC");
+	assert_eq!(list.clone().map_string(|s| s.to_uppercase()), list);
+	
+	let panic = std::panic::catch_unwind(|| string.unwrap_dict()).unwrap_err();
+	assert_eq!(panic.downcast_ref::<String>().unwrap(), "called unwrap_dict on a string");
+}
+
+
+#[test]
+fn parse_partial() {
+	let source = "# doc\n{a=1}\n[x y] \"z\"";
+	
+	let (first, rest, end) = parse_value_partial(source).unwrap();
+	assert_eq!(first, parse_value("{a=1}").unwrap());
+	assert_eq!(end, ParseEnd {line: 2, col: 6, offset: 11});
+	assert_eq!(rest, "\n[x y] \"z\"");
+	
+	let (second, rest, end) = parse_value_partial(rest).unwrap();
+	assert_eq!(second, parse_value("[x y]").unwrap());
+	assert_eq!(end, ParseEnd {line: 2, col: 6, offset: 6});
+	
+	let (third, rest, _) = parse_value_partial(rest).unwrap();
+	assert_eq!((third, rest), (JsefValue::from("z"), ""));
+	assert!(parse_value_partial(rest).is_err());
+}
+
+
+#[test]
+fn prune_empty() {
+	let mut value = JsefValue::Dict(parse_dict("a={} b=[{} x []] c.d={} e=\"\"").unwrap());
+	value.prune_empty();
+	assert_eq!(value, parse_dict("b=[x] e=\"\"").unwrap());
+	
+	let mut value = JsefValue::List(parse_list("{a=[]} []").unwrap());
+	value.prune_empty();
+	assert_eq!(value, JsefValue::new_list());
+	
+	let mut value = JsefValue::from("x");
+	value.prune_empty();
+	assert_eq!(value, "x");
+	
+	assert!(JsefValue::new_dict().is_empty_container());
+	assert!(!JsefValue::new_string().is_empty_container());
+	
+	let mut deep = JsefValue::from("leaf");
+	for _ in 0..10_000 {
+		deep = JsefValue::from_pairs([("k", deep), ("e", JsefValue::new_list())]);
+	}
+	
+	// into_flat takes the tree apart without recursion, unlike dropping it
+	deep.prune_empty();
+	let leaves: Vec<_> = deep.into_flat().collect();
+	assert_eq!(leaves.len(), 1);
+	assert_eq!(leaves[0].0.len(), "k.".len() * 10_000 - 1);
+}
+
+
+#[test]
+fn canonicalize_bools() {
+	let mut value = JsefValue::Dict(parse_dict("a=yes b=[Off ON maybe] yes=no c.d=TRUE").unwrap());
+	super::canonicalize_bools(&mut value, &["yes", "on", "true"], &["no", "off"]);
+	
+	assert_eq!(value, parse_dict("a=true b=[false true maybe] yes=false c.d=true").unwrap());
+}
+
+
+#[test]
+fn quote_keys_values() {
+	let dict = parse_dict("a.b=x").unwrap();
+	
+	assert_eq!(compose_dict(&dict, &ComposeOpts::COMPACT.quote_keys(true)).unwrap(), "\"a\".\"b\"=x");
+	assert_eq!(compose_dict(&dict, &ComposeOpts::COMPACT.quote_values(true)).unwrap(), "a.b=\"x\"");
+	assert_eq!(compose_dict(&dict, &ComposeOpts::COMPACT.force_quotes(true)).unwrap(), "\"a\".\"b\"=\"x\"");
+	
+	let dict = parse_dict("key=value list=[a b] nested={c=d}").unwrap();
+	let composed = compose_dict(&dict, &ComposeOpts::PRETTY.quote_keys(true)).unwrap();
+	assert!(!composed.contains("\"value\""));
+	assert_eq!(parse_dict(&composed).unwrap(), dict);
+}
+
+
+#[test]
+fn merge_str() {
+	let mut value = JsefValue::Dict(parse_dict("a.b=1 a.c=2 l=[x y] s=z").unwrap());
+	value.merge_str("a.c=3 a.d=4 l=[w] n=5").unwrap();
+	assert_eq!(value, parse_dict("a.b=1 a.c=3 a.d=4 l=[w] s=z n=5").unwrap());
+	
+	assert_eq!(value.merge_str("a="), Err(JsefErr::new(JsefErrType::Unexpected(None), 1, 3, 2).in_key("a")));
+	
+	let mut value = JsefValue::new_list();
+	let err = value.merge_str("a=1").unwrap_err();
+	assert_eq!(err.err, JsefErrType::TypeConflict {path: String::new(), expected: JsefKind::Dict, found: JsefKind::List});
+}
+
+
+#[test]
+fn tabular() {
+	let opts = ComposeOpts::PRETTY.tabular(true);
+	
+	let list = parse_list("{name=a port=80} {port=8080 name=\"b c\"}").unwrap();
+	let composed = compose_list(&list, &opts).unwrap();
+	assert_eq!(composed, "{ name = a     port = 80 }\n{ name = \"b c\" port = 8080 }");
+	assert_eq!(parse_list(&composed).unwrap(), list);
+	
+	let composed = compose_list(&list, &opts.clone().dense(true)).unwrap();
+	assert_eq!(composed, "{name=a     port=80}\n{name=\"b c\" port=8080}");
+	
+	let mixed = parse_list("{a=1} {b=2}").unwrap();
+	assert_eq!(compose_list(&mixed, &opts), compose_list(&mixed, &ComposeOpts::PRETTY));
+	
+	let nested = parse_list("{a=[1]} {a=[2]}").unwrap();
+	assert_eq!(compose_list(&nested, &opts), compose_list(&nested, &ComposeOpts::PRETTY));
+}
+
+
+#[test]
+fn remove() {
+	let mut value = JsefValue::Dict(parse_dict("a=1 b.c=2").unwrap());
+	
+	assert_eq!(value.remove("b"), Some(parse_value("{c=2}").unwrap()));
+	assert_eq!(value.remove("b"), None);
+	assert_eq!(value, parse_dict("a=1").unwrap());
+	assert_eq!(JsefValue::from("a").remove("a"), None);
+}
+
+
+#[test]
+fn numeric_keys() {
+	let dict = parse_dict("0 = 1 -1.5 = x 1e3.2 = y").unwrap();
+	
+	assert_eq!(dict["0"], "1");
+	assert_eq!(dict["-1"].as_dict().unwrap()["5"], "x");
+	assert_eq!(dict["1e3"].as_dict().unwrap()["2"], "y");
+	
+	let single = parse_dict("0=1").unwrap();
+	for opts in [ComposeOpts::PRETTY, ComposeOpts::COMPACT] {
+		let composed = compose_dict(&single, &opts).unwrap();
+		assert!(composed.starts_with('0'));
+		assert_eq!(parse_dict(&composed).unwrap(), single);
+	}
+}
+
+
+#[test]
+fn parse_value_from_frames() {
+	use std::io::{BufReader, Read};
+	
+	let source = "# frames\n{a=\"}\" b=[1 # ]\n]} word\"rest\" \"x\"8";
+	
+	for capacity in [1, 3, 64] {
+		let mut reader = BufReader::with_capacity(capacity, source.as_bytes());
+		
+		assert_eq!(parse_value_from(&mut reader).unwrap(), parse_value("{a=\"}\" b=[1]}").unwrap());
+		assert_eq!(parse_value_from(&mut reader).unwrap(), "word");
+		assert_eq!(parse_value_from(&mut reader).unwrap(), "rest");
+		
+		let mut rest = String::new();
+		reader.read_to_string(&mut rest).unwrap();
+		assert_eq!(rest, " \"x\"8");
+	}
+	
+	let mut reader = "\n[a".as_bytes();
+	assert_eq!(parse_value_from(&mut reader), Err(JsefErr::new(JsefErrType::Mismatch(']', None), 2, 3, 3)));
+}
+
+
+#[test]
+fn stream_framing() {
+	let mut reader = "\"a\"\"b\"[c]{d=e}w[x]".as_bytes();
+	
+	let frames: Vec<_> = (0..6).map(|_| parse_value_from(&mut reader).unwrap()).collect();
+	assert_eq!(frames, [
+		JsefValue::from("a"),
+		JsefValue::from("b"),
+		parse_value("[c]").unwrap(),
+		parse_value("{d=e}").unwrap(),
+		JsefValue::from("w"),
+		parse_value("[x]").unwrap(),
+	]);
+	
+	assert!(reader.is_empty());
+	assert_eq!(parse_value_from(&mut reader), Err(JsefErr::new(JsefErrType::Unexpected(None), 1, 1, 0)));
+}
+
+
+#[test]
+fn flat_map() {
+	use std::collections::HashMap;
+	
+	let value = JsefValue::Dict(parse_dict("a.b=1 a.c=[x {}] d={}").unwrap());
+	let map = value.to_flat_map();
+	
+	let expected: HashMap<_, _> = [("a.b", "1"), ("a.c.0", "x")]
+		.into_iter()
+		.map(|(k, v)| (k.to_owned(), v.to_owned()))
+		.collect();
+	
+	assert_eq!(map, expected);
+	assert_eq!(JsefValue::from_flat_map(map), parse_dict("a.b=1 a.c.0=x").unwrap());
+	
+	let conflict = HashMap::from([("a.b".to_owned(), "y".to_owned()), ("a".to_owned(), "x".to_owned())]);
+	assert_eq!(JsefValue::from_flat_map(conflict), parse_dict("a.b=y").unwrap());
+}
+
+
+#[test]
+fn clone_from() {
+	let template = JsefValue::Dict(parse_dict("a=x b=[1 2 3] c.d=e").unwrap());
+	let mut dst = JsefValue::Dict(parse_dict("a=\"a longer string\" b=[4] c=[] z=old").unwrap());
+	
+	let capacity = dst.as_dict().unwrap()["a"].as_string().unwrap().capacity();
+	template.deep_clone_into(&mut dst);
+	
+	assert_eq!(dst, template);
+	assert_eq!(dst.as_dict().unwrap()["a"].as_string().unwrap().capacity(), capacity);
+	
+	let mut scalar = JsefValue::from("s");
+	scalar.clone_from(&template);
+	assert_eq!(scalar, template);
+}
+
+
+#[test]
+fn parse_prefix() {
+	let source = "{title=\"Front matter\"}\n# Heading";
+	let (value, len) = parse_value_prefix(source).unwrap();
+	
+	assert_eq!(value, parse_value("{title=\"Front matter\"}").unwrap());
+	assert_eq!(&source[len..], "\n# Heading");
+	assert_eq!(parse_value_prefix("  word!"), Ok((JsefValue::from("word!"), 7)));
+}
+
+
+#[test]
+fn owned_opts() {
+	let opts = ComposeOpts::PRETTY.indent("  ").prelude("generated");
+	let owned = ComposeOptsOwned::from(&opts);
+	
+	assert_eq!(owned.as_opts(), opts);
+	assert_ne!(opts, ComposeOpts::PRETTY);
+	assert_eq!("compact".parse::<ComposeOpts>().unwrap(), ComposeOpts::COMPACT);
+	
+	let value = parse_value("{a=[b c]}").unwrap();
+	assert_eq!(compose_value(&value, &owned.as_opts()), compose_value(&value, &opts));
+}
+
+
+#[test]
+fn owned_builders() {
+	let prelude = String::from("generated");
+	let owned = ComposeOptsOwned::from(ComposeOpts::PRETTY)
+		.indent("  ")
+		.prelude(prelude.clone())
+		.no_list_separator()
+		.tabular(true);
+	
+	assert_eq!(owned.as_opts(), ComposeOpts::PRETTY.indent("  ").prelude(&prelude).tabular(true));
+}
+
+
+#[cfg(feature = "json")]
+#[test]
+fn parse_to_json() {
+	use serde_json::json;
+	
+	let value = super::parse_to_json("{name=jsef a.b=[\"1\" 2] a.c=\"x\\ty\"}").unwrap();
+	assert_eq!(value, json!({"name": "jsef", "a": {"b": ["1", "2"], "c": "x\ty"}}));
+	
+	assert!(super::parse_to_json("{a=").is_err());
+}
+
+
+#[test]
+fn key_errors() {
+	let err = parse_dict("a=0 b c=2").unwrap_err();
+	assert_eq!(err, JsefErr::new(JsefErrType::Mismatch('=', Some('c')), 1, 7, 6).in_key("b"));
+	assert_eq!(err.to_string(), "JSeF error at line 1, col 7: while parsing key \"b\": expected '=', got 'c'");
+	
+	let err = parse_dict("outer={inner=[0}").unwrap_err();
+	assert_eq!(err.key.as_deref(), Some("inner"));
+	assert_eq!(err.to_diagnostic().message, "while parsing key \"inner\": expected ']', got '}'");
+}
+
+
+#[test]
+fn max_output_bytes() {
+	let value = parse_value("[aaa bbb ccc]").unwrap();
+	let opts = ComposeOpts::COMPACT.max_output_bytes(8);
+	let err = JsefErr::new(JsefErrType::OutputTooLong(8), 1, 9, 8);
+	
+	assert_eq!(compose_value(&value, &opts), Err(err.clone()));
+	assert_eq!(composed_len(&value, &opts), Err(err));
+	
+	let mut sink = String::new();
+	assert!(compose_value_sink(&value, &opts, &mut sink).is_err());
+	assert_eq!(sink, "[aaa bbb");
+	
+	let opts = opts.max_output_bytes(13);
+	assert_eq!(compose_value(&value, &opts).unwrap(), "[aaa bbb ccc]");
+}
+
+
+#[test]
+fn unicode_escapes() {
+	use JsefErrType::*;
+	
+	assert_eq!(parse_value("\"\\u{41}\\u{1F600}x\"").unwrap(), JsefValue::from("A\u{1F600}x"));
+	
+	assert_eq!(parse_value("\"\\u41\""), Err(JsefErr::new(Unexpected(Some('4')), 1, 4, 3)));
+	assert_eq!(parse_value("\"\\u{4g}\""), Err(JsefErr::new(Unexpected(Some('g')), 1, 6, 5)));
+	assert_eq!(parse_value("\"\\u{}\""), Err(JsefErr::new(Unexpected(Some('}')), 1, 5, 4)));
+	assert_eq!(parse_value("\"ab\\u{D800}\""), Err(JsefErr::new(InvalidCodepoint(0xD800), 1, 4, 3)));
+	assert_eq!(parse_value("\"\\u{110000}\""), Err(JsefErr::new(InvalidCodepoint(0x110000), 1, 2, 1)));
+	assert_eq!(parse_value("\"\\u{1000000}\""), Err(JsefErr::new(Unexpected(Some('0')), 1, 11, 10)));
+}
+
+
+#[test]
+fn escape_controls() {
+	let value = JsefValue::from("bell\x07 esc\x1b\n");
+	let opts = ComposeOpts::COMPACT.escape_controls(true);
+	
+	let composed = compose_value(&value, &opts).unwrap();
+	assert_eq!(composed, "\"bell\\u{7} esc\\u{1B}\\n\"");
+	assert_eq!(parse_value(&composed).unwrap(), value);
+	assert_eq!(composed_len(&value, &opts), Ok(composed.len()));
+	
+	assert_eq!(compose_value(&value, &ComposeOpts::COMPACT).unwrap(), "\"bell\x07 esc\x1b\\n\"");
+}
+
+
+#[test]
+fn max_depth() {
+	let deep = "[0 [1 [2 [3]]]]";
+	assert_eq!(parse_value(deep), Err(JsefErr::new(JsefErrType::MaxDepth, 1, 7, 6)));
+	
+	let opts = ParseOpts::DEFAULT.max_depth(4);
+	assert_eq!(parse_value_with(deep, &opts).unwrap().as_list().map(Vec::len), Some(2));
+	
+	let opts = ParseOpts::DEFAULT.max_depth(1);
+	assert_eq!(parse_value_with("[0 [1]]", &opts), Err(JsefErr::new(JsefErrType::MaxDepth, 1, 4, 3)));
+	assert_eq!(parse_dict_with("a=[0]", &opts).unwrap().len(), 1);
+}
+
+
+#[test]
+fn parse_list_and_dict_from_read() {
+	use std::io::{self, Cursor, Read};
+	
+	struct Failing<'a>(&'a [u8]);
+	
+	impl Read for Failing<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			if self.0.is_empty() {
+				return Err(io::ErrorKind::ConnectionReset.into());
+			}
+			
+			self.0.read(buf)
+		}
+	}
+	
+	let dict = parse_dict_from(Cursor::new("a=0\nb.c=1")).unwrap();
+	assert_eq!(dict, parse_dict("a=0 b.c=1").unwrap());
+	assert_eq!(parse_list_from(Cursor::new("x [y]")).unwrap(), parse_list("x [y]").unwrap());
+	
+	let err = JsefErr::new(JsefErrType::Io(io::ErrorKind::ConnectionReset), 2, 3, 6);
+	assert_eq!(parse_dict_from(Failing(b"a=0\nb=")), Err(err));
+	assert_eq!(parse_list_from(&[0xff][..]), Err(JsefErr::new(JsefErrType::Io(io::ErrorKind::InvalidData), 1, 1, 0)));
+}
+
+
+#[test]
+fn parse_list_and_dict_from_trickling_reader() {
+	use std::io::{self, Read};
+	
+	/// Hands out a single byte per read, so every frame spans many reads.
+	struct Trickle<'a>(&'a [u8]);
+	
+	impl Read for Trickle<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			let len = buf.len().min(self.0.len()).min(1);
+			self.0.read(&mut buf[..len])
+		}
+	}
+	
+	const DICTS: &[&str] = &[
+		"", "# only a comment", "a=0\nb.c=1 b.d=[x {y=z}]\n", "a=x a.b=y", "\"k ey\" . \"q\" = \"v\\\"\" # c",
+		"a=[x y", "a=b}", "a=b.c=d", "a.=b", "a\n=\n[\n]\nb=\n\"x\ny\" c=[[[d]]]", "[x]", "a b=c", "a=b\n  ]",
+	];
+	
+	for source in DICTS {
+		assert_eq!(parse_dict_from(Trickle(source.as_bytes())), parse_dict(source), "{source:?}");
+	}
+	
+	const LISTS: &[&str] = &["", "x [y {a=b}] \"z\"\n# end", "a ] b", "a\n[b c", "a\n\n b=c", "[[[x]]]", "ä ö ü"];
+	
+	for source in LISTS {
+		assert_eq!(parse_list_from(Trickle(source.as_bytes())), parse_list(source), "{source:?}");
+	}
+}
+
+
+#[test]
+fn compose_to_writer() {
+	use std::io;
+	
+	let dict = parse_dict("a=0 b=[x y]").unwrap();
+	let opts = ComposeOpts::PRETTY.fold_dicts(false);
+	
+	let mut out = Vec::new();
+	compose_dict_to(&dict, &opts, &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), compose_dict(&dict, &opts).unwrap());
+	
+	let list = parse_list("first\nsecond third").unwrap();
+	let mut buf = [0; 8];
+	let err = JsefErr::new(JsefErrType::Io(io::ErrorKind::WriteZero), 2, 1, 6);
+	assert_eq!(compose_list_to(&list, &ComposeOpts::PRETTY, &mut buf[..]), Err(err));
+	assert_eq!(&buf, b"first\nse");
+	
+	let mut out = Vec::new();
+	compose_value_to(&JsefValue::from("a b"), &opts, &mut out).unwrap();
+	assert_eq!(out, b"\"a b\"");
+	
+	struct NoFlush;
+	
+	impl io::Write for NoFlush {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			Ok(buf.len())
+		}
+		
+		fn flush(&mut self) -> io::Result<()> {
+			Err(io::ErrorKind::BrokenPipe.into())
+		}
+	}
+	
+	let err = JsefErr::new(JsefErrType::Io(io::ErrorKind::BrokenPipe), 3, 6, 18);
+	assert_eq!(compose_list_to(&list, &ComposeOpts::PRETTY, NoFlush), Err(err));
+}
+
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde() {
+	use serde_json::json;
+	
+	let value = parse_value("{name=jsef tags=[a b] nested.x=\"1\"}").unwrap();
+	let json = serde_json::to_value(&value).unwrap();
+	assert_eq!(json, json!({"name": "jsef", "tags": ["a", "b"], "nested": {"x": "1"}}));
+	assert_eq!(serde_json::from_value::<JsefValue>(json).unwrap(), value);
+	
+	let lossy: JsefValue = serde_json::from_str("[1, -2, 1.5, true, \"x\"]").unwrap();
+	assert_eq!(lossy, parse_value("[1 -2 \"1.5\" true x]").unwrap());
+	assert_eq!(serde_json::from_str::<JsefValue>("[null, 1.0]").unwrap(), parse_value("[\"\" \"1.0\"]").unwrap());
+	
+	let diagnostic = parse_value("[0 1 2}").unwrap_err().to_diagnostic();
+	assert_eq!(serde_json::to_value(&diagnostic).unwrap(), json!({
+		"message": "expected ']', got '}'",
+		"line": 1, "col": 7, "offset": 6, "file": null,
+		"code": "mismatch",
+	}));
+}
+
+
+#[cfg(feature = "json")]
+#[test]
+fn json_conversions() {
+	use serde_json::{json, Value};
+	
+	let json = json!({"name": "jsef", "list": [1, 2.5, true, null], "dict": {"a": {"b": "c"}}});
+	let value = JsefValue::from(json);
+	assert_eq!(value, parse_value("{name=jsef list=[1 \"2.5\" true \"\"] dict.a.b=c}").unwrap());
+	
+	let back = Value::from(value.clone());
+	assert_eq!(back, json!({"name": "jsef", "list": ["1", "2.5", "true", ""], "dict": {"a": {"b": "c"}}}));
+	assert_eq!(JsefValue::from(back), value);
+}
+
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[test]
+fn serde_and_json_agree() {
+	let source = r#"{"a": [null, 1, -2, 1.0, 2.5, -0.0, 1e20, 1e-7, 0.0001, 123456789.125, true, "x"], "b": {"c": null}}"#;
+	let through_serde: JsefValue = serde_json::from_str(source).unwrap();
+	let through_json = JsefValue::from(serde_json::from_str::<serde_json::Value>(source).unwrap());
+	assert_eq!(through_serde, through_json);
+}
+
+
+#[test]
+fn index() {
+	let mut value = parse_value("{a.b=[x y] c=z}").unwrap();
+	
+	assert_eq!(value["a"]["b"][1], "y");
+	assert_eq!(value["c"], "z");
+	
+	value["a"]["b"][0] = JsefValue::from("w");
+	value["new"] = JsefValue::from("v");
+	assert_eq!(value, parse_value("{a.b=[w y] c=z new=v}").unwrap());
+	
+	assert_eq!(&mut value["d"], "");
+	assert_eq!(value["d"], "");
+	
+	assert!(std::panic::catch_unwind(|| value["missing"].clone()).is_err());
+	assert!(std::panic::catch_unwind(|| parse_value("[x]").unwrap()["a"].clone()).is_err());
+}
+
+
+#[test]
+fn get_path() {
+	let mut value = parse_value("{a.b=[x] a.c=y d=z}").unwrap();
+	
+	assert_eq!(value.get_path("a.c"), Some(&JsefValue::from("y")));
+	assert_eq!(value.get_path("a.b"), Some(&parse_value("[x]").unwrap()));
+	assert_eq!(value.get_path("a.b.0"), None);
+	assert_eq!(value.get_path("d.e"), None);
+	assert_eq!(value.get_path("a.missing"), None);
+	
+	*value.get_path_mut("a.c").unwrap() = JsefValue::from("w");
+	assert_eq!(value, parse_value("{a.b=[x] a.c=w d=z}").unwrap());
+	assert!(value.get_path_mut("d.e").is_none());
+}
+
+
+#[test]
+fn set_path() {
+	let mut value = parse_value("{a.b=x}").unwrap();
+	assert_eq!(value.set_path("a.c", JsefValue::from("y")), None);
+	assert_eq!(value, parse_value("{a.b=x\na.c=y}").unwrap());
+	
+	assert_eq!(value.set_path("a.b", JsefValue::new_list()), Some(JsefValue::from("x")));
+	assert_eq!(value.set_path("a.b.c", JsefValue::from("z")), None);
+	assert_eq!(value.set_path("a.c.d", JsefValue::from("w")), None);
+	assert_eq!(value, parse_value("{a.b=[] a.c=y a.b.c=z a.c.d=w}").unwrap());
+	
+	assert_eq!(value.set_path("top", JsefValue::from("v")), None);
+	assert_eq!(value["top"], "v");
+}
+
+#[test]
+fn path_sep() {
+	let mut value = parse_value("{\"v1.2\"={x=y}}").unwrap();
+	
+	assert_eq!(value.get_path_sep("v1.2/x", "/"), Some(&JsefValue::from("y")));
+	assert_eq!(value.get_path("v1.2.x"), None);
+	
+	*value.get_path_mut_sep("v1.2/x", "/").unwrap() = JsefValue::from("z");
+	assert_eq!(value.set_path_sep("v1.2/w", "/", JsefValue::from("v")), None);
+	assert_eq!(value.set_path_sep("v1.3/x", "/", JsefValue::from("u")), None);
+	assert_eq!(value, parse_value("{\"v1.2\"={x=z w=v} \"v1.3\"={x=u}}").unwrap());
+}
+
+
+#[test]
+fn reject_duplicates() {
+	use JsefErrType::DuplicateKey;
+	
+	let opts = ParseOpts::DEFAULT.reject_duplicates(true);
+	
+	assert_eq!(parse_dict_with("a=1\n a=2", &opts), Err(JsefErr::new(DuplicateKey("a".to_owned()), 2, 2, 5)));
+	assert_eq!(parse_dict_with("a.b=1 a.b=2", &opts), Err(JsefErr::new(DuplicateKey("b".to_owned()), 1, 9, 8)));
+	assert_eq!(parse_dict_with("a=1 a.b=2", &opts), Err(JsefErr::new(DuplicateKey("a".to_owned()), 1, 5, 4)));
+	assert_eq!(parse_dict_with("a.b=1 a.c=2", &opts), parse_dict("a.b=1 a.c=2"));
+	assert_eq!(parse_dict_with("a={} b={a=1 b=2}", &opts), parse_dict("a={} b={a=1 b=2}"));
+	
+	assert_eq!(parse_dict("a=1 a=2"), parse_dict("a=2"));
+}
+
+
+#[test]
+fn sort_keys() {
+	let dict = parse_dict("b=1 d.y=2 a={z=3 c=4} c=5").unwrap();
+	let opts = ComposeOpts::COMPACT.sort_keys(true);
+	
+	assert_eq!(compose_dict(&dict, &opts).unwrap(), "a={c=4 z=3} b=1 c=5 d.y=2");
+	assert_eq!(compose_dict(&dict, &opts.clone().fold_dicts(false)).unwrap(), "a={c=4 z=3} b=1 c=5 d={y=2}");
+	assert_eq!(composed_len(&JsefValue::Dict(dict), &opts), Ok(27));
+}
+
+
+#[test]
+fn compose_into() {
+	let dict = parse_dict("a=0 b=[x y]").unwrap();
+	let list = parse_list("x {y=z}").unwrap();
+	
+	let mut buf = String::from("# header\n");
+	compose_dict_into(&dict, &ComposeOpts::PRETTY, &mut buf).unwrap();
+	compose_list_into(&list, &ComposeOpts::COMPACT, &mut buf).unwrap();
+	
+	let expected = format!("# header\n{}{}",
+		compose_dict(&dict, &ComposeOpts::PRETTY).unwrap(),
+		compose_list(&list, &ComposeOpts::COMPACT).unwrap(),
+	);
+	assert_eq!(buf, expected);
+	
+	let deep = parse_value("[[x]]").unwrap();
+	let opts = ComposeOpts::COMPACT.max_output_bytes(2);
+	assert_eq!(compose_value_into(&deep, &opts, &mut buf), Err(JsefErr::new(JsefErrType::OutputTooLong(2), 1, 3, 2)));
+	assert_eq!(buf, expected);
+}
+
+
+#[test]
+fn from_str() {
+	let value: JsefValue = "{a=[x y]}".parse().unwrap();
+	assert_eq!(value, parse_value("{a=[x y]}").unwrap());
+	
+	assert_eq!("word".parse::<JsefValue>().unwrap(), "word");
+	assert_eq!("a=x".parse::<JsefValue>(), parse_value("a=x"));
+}
+
+
+#[test]
+fn jsef_macro() {
+	let name = "jsef";
+	let tags = JsefValue::from_values(["a", "b"]);
+	
+	let value = jsef!({
+		name: name,
+		version: 1.50,
+		"quoted key": "x y",
+		enabled: true,
+		tags: tags,
+		nested: {list: [0], expr: (format!("{name}!"))},
+		(name.to_uppercase()): 'c',
+	});
+	
+	let expected = parse_dict("
+		name=jsef version=\"1.50\" \"quoted key\"=\"x y\" enabled=true tags=[a b]
+		nested={list=[0] expr=jsef!} JSEF=c
+	").unwrap();
+	
+	assert_eq!(value, expected);
+	assert_eq!(jsef!([[], {},]), parse_value("[[] {}]").unwrap());
+	assert_eq!(jsef!("word"), "word");
+}
+
+
+#[test]
+fn from_iter_into() {
+	let words = vec!["a", "b c"];
+	let list: JsefList = JsefValue::list_from_iter(words);
+	assert_eq!(list, parse_list("a \"b c\"").unwrap());
+	
+	let dict: JsefDict = JsefValue::dict_from_iter([("x", String::from("1")), ("y", String::from("2"))]);
+	assert_eq!(dict, parse_dict("x=1 y=2").unwrap());
+}
+
+
+#[test]
+fn numeric_accessors() {
+	let list = parse_list("+12 -3 \"1.5\" \" 4\" true False [1] 18446744073709551615").unwrap();
+	
+	assert_eq!(list[0].as_i64(), Some(12));
+	assert_eq!(list[1].as_i64(), Some(-3));
+	assert_eq!(list[1].as_u64(), None);
+	assert_eq!(list[2].as_f64(), Some(1.5));
+	assert_eq!(list[2].as_i64(), None);
+	assert_eq!(list[3].as_i64(), None);
+	assert_eq!(list[4].as_bool(), Some(true));
+	assert_eq!(list[5].as_bool(), None);
+	assert_eq!(list[6].as_i64(), None);
+	assert_eq!(list[6].as_bool(), None);
+	assert_eq!(list[7].as_u64(), Some(u64::MAX));
+	assert_eq!(list[7].as_i64(), None);
+}
+
+
+#[test]
+fn comments() {
+	let source = "\
+		# Server settings\n\
+		server = {\n\
+			# The port\n\
+			# of the server\n\
+			port = 80 # trailing comments are dropped\n\
+			hosts = [\n\
+				# primary\n\
+				a\n\
+				b\n\
+				# dropped at the end\n\
+			]\n\
+		}\n\
+		# Name\n\
+		name = x\n\
+	";
+	
+	let (mut dict, comments) = parse_dict_with_comments(source).unwrap();
+	assert_eq!(dict, parse_dict(source).unwrap());
+	
+	let mut sorted: Vec<_> = comments.iter().collect();
+	sorted.sort();
+	assert_eq!(format!("{sorted:?}"), r#"[("name", [" Name"]), ("server", [" Server settings"]), ("server.hosts.0", [" primary"]), ("server.port", [" The port", " of the server"])]"#);
+	
+	dict.insert("added".to_owned(), JsefValue::from("y"));
+	let opts = ComposeOpts::PRETTY.sort_keys(true);
+	let composed = compose_dict_with_comments(&dict, &opts, &comments).unwrap();
+	
+	assert_eq!(composed, "added = y\n# Name\nname = x\n# Server settings\nserver = {\n\thosts = [\n\t\t# primary\n\t\ta\n\t\tb\n\t]\n\t# The port\n\t# of the server\n\tport = 80\n}");
+	assert_eq!(parse_dict_with_comments(&composed).unwrap().1, comments);
+	
+	let compact = compose_dict_with_comments(&dict, &ComposeOpts::COMPACT.sort_keys(true), &comments).unwrap();
+	assert_eq!(compact, compose_dict(&dict, &ComposeOpts::COMPACT.sort_keys(true)).unwrap());
+	
+	let slashes = compose_dict_with_comments(&dict, &opts.comment_lead("//"), &comments).unwrap();
+	assert_eq!(slashes, composed.replace('#', "//"));
+	assert_eq!(parse_dict_with(&slashes, &ParseOpts::DEFAULT.comment_lead("//")).unwrap(), dict);
+}
+
+
+#[test]
+fn comment_lead() {
+	let opts = ParseOpts::DEFAULT.comment_lead("//");
+	
+	let dict = parse_dict_with("// comment\ncolor = #fff // trailing\nurl = \"a/b\"", &opts).unwrap();
+	assert_eq!(dict, parse_dict("color = \"#fff\" url = \"a/b\"").unwrap());
+	assert_eq!(parse_dict_with("a = b/c", &opts), Err(JsefErr::new(JsefErrType::NotEof('/'), 1, 6, 5)));
+	
+	let opts = ParseOpts::DEFAULT.comment_lead(";");
+	assert_eq!(parse_value_with("[a;b\nc]", &opts).unwrap(), parse_value("[a c]").unwrap());
+	
+	let opts = ParseOpts::DEFAULT.comment_lead("");
+	assert_eq!(parse_value_with("[#a b#]", &opts).unwrap(), parse_value("[\"#a\" \"b#\"]").unwrap());
+	
+	let tokens: Vec<_> = Lexer::new("x //y", &ParseOpts::DEFAULT.comment_lead("//"))
+		.map(|token| token.unwrap().kind)
+		.collect();
+	assert_eq!(tokens, [TokenKind::Word("x".into()), TokenKind::Comment("y")]);
+}
+
+
+#[test]
+fn form_feed_vertical_tab() {
+	let value = JsefValue::from("page\x0cbreak\x0bline");
+	let composed = compose_value(&value, &ComposeOpts::COMPACT).unwrap();
+	
+	assert_eq!(composed, "\"page\\fbreak\\vline\"");
+	assert_eq!(parse_value(&composed).unwrap(), value);
+	assert_eq!(composed_len(&value, &ComposeOpts::COMPACT), Ok(composed.len()));
+}
+
+
+#[test]
+fn strict_escapes() {
+	let opts = ParseOpts::DEFAULT.strict_escapes(true);
+	let known = "\"\\\\ \\\" \\n \\t \\r \\f \\v \\0 \\u{41}\"";
+	
+	assert_eq!(parse_value_with(known, &opts), parse_value(known));
+	assert_eq!(parse_value_with("[ok\n\"a\\x41\"]", &opts), Err(JsefErr::new(JsefErrType::InvalidEscape('x'), 2, 3, 6)));
+	assert_eq!(parse_value("\"\\q\"").unwrap(), "q");
+	
+	let err = parse_value_with("\"\\q\"", &opts).unwrap_err();
+	assert_eq!(err.err.to_string(), "unknown escape sequence '\\q'");
+}
+
+
+#[test]
+fn byte_offsets() {
+	let err = parse_value("[\"é\" }").unwrap_err();
+	assert_eq!((err.line, err.col, err.byte), (1, 6, 6));
+	assert_eq!(err.to_diagnostic().offset, 6);
+	
+	let errs: Vec<_> = super::parse_lines("a\r\n[b\r\n".as_bytes()).filter_map(Result::err).collect();
+	assert_eq!(errs, [JsefErr::new(JsefErrType::Mismatch(']', None), 2, 3, 5)]);
+}
+
+
+#[test]
+fn render_with_source() {
+	let source = "a=1\nb=[x\n\ty}";
+	let err = parse_dict(source).unwrap_err();
+	assert_eq!(err.render_with_source(source), format!("{err}\n3 | \ty}}\n  | \t ^"));
+	
+	let source = "a=\"x\n";
+	let err = parse_dict(source).unwrap_err();
+	assert_eq!(err.render_with_source(source), format!("{err}\n1 | a=\"x\n  |     ^"));
+	
+	let err = JsefErr::new(JsefErrType::MaxDepth, 0, 0, 0);
+	assert_eq!(err.render_with_source(source), err.to_string());
+}
+
+
+#[test]
+fn deep_nesting() {
+	const DEPTH: usize = 10_000;
+	
+	let source = "[".repeat(DEPTH) + &"]".repeat(DEPTH);
+	let opts = ParseOpts::DEFAULT.max_depth(DEPTH);
+	let (_, info) = parse_value_with_info(&source, &opts).unwrap();
+	assert_eq!(info.peak_depth, DEPTH);
+	
+	let opts = opts.max_depth(DEPTH - 1);
+	let err = JsefErr::new(JsefErrType::MaxDepth, 1, DEPTH, DEPTH - 1);
+	assert_eq!(parse_value_with(&source, &opts), Err(err));
+}
+
+
+#[test]
+fn parse_from_bytes() {
+	let dict = parse_dict_from_bytes(b"\xEF\xBB\xBFa=1 b=[x]").unwrap();
+	assert_eq!(dict, parse_dict("a=1 b=[x]").unwrap());
+	assert_eq!(parse_list_from(&b"\xEF\xBB\xBFx y"[..]).unwrap(), parse_list("x y").unwrap());
+	assert_eq!(parse_value_from_bytes("\"é\"".as_bytes()).unwrap(), JsefValue::from("é"));
+	
+	let err = |line, col, byte| JsefErr::new(JsefErrType::Io(std::io::ErrorKind::InvalidData), line, col, byte);
+	assert_eq!(parse_value_from_bytes(b"\"\xC3\""), Err(err(1, 2, 1)));
+	assert_eq!(parse_dict_from_bytes(b"a=\xE2\x82"), Err(err(1, 3, 2)));
+	assert_eq!(parse_dict_from_bytes(b"\xEF\xBB\xBFa=\"\xC3\xA9\"\nb=\xE2\x82"), Err(err(2, 3, 9)));
+	assert_eq!(parse_dict_from(&b"a=\"\xC3\xA9\"\nb=\xE2\x82"[..]), Err(err(2, 3, 9)));
+	assert_eq!(parse_list_from(&b"x\n  y \xFF"[..]), Err(err(2, 5, 6)));
+	assert_eq!(parse_value_from(&mut &b"\n[a \xFF]"[..]), Err(err(2, 4, 4)));
+	
+	let mut reader = std::io::BufReader::new(&b"\xEF\xBB\xBF{a=1} x"[..]);
+	assert_eq!(parse_value_from(&mut reader).unwrap(), parse_value("{a=1}").unwrap());
+	assert_eq!(parse_value_from(&mut reader).unwrap(), "x");
+}
+
+
+#[test]
+fn merge() {
+	let mut config = parse_value("{log={level=info file=main_log} ports=[1 2] name=x}").unwrap();
+	config.merge(parse_value("{log.level=debug ports=[3] extra=y}").unwrap());
+	assert_eq!(config, parse_value("{log={level=debug file=main_log} ports=[3] name=x extra=y}").unwrap());
+	
+	config.merge(parse_value("{log=off name={first=x}}").unwrap());
+	assert_eq!(config, parse_value("{log=off ports=[3] name={first=x} extra=y}").unwrap());
+	
+	config.merge(JsefValue::string_from("plain"));
+	assert_eq!(config, JsefValue::string_from("plain"));
+}
+
+
+#[test]
+fn diff() {
+	let old = parse_value("{name=x ports=[1 2 3] log={level=info file=main_log} gone=y}").unwrap();
+	let new = parse_value("{name=z ports=[1 4] log={level=info} extra=[a]}").unwrap();
+	
+	let mut changes = old.diff(&new);
+	changes.sort_by(|a, b| a.path().cmp(b.path()));
+	
+	assert_eq!(changes, [
+		Change::Added("extra".to_owned(), &new["extra"]),
+		Change::Removed("gone".to_owned(), &old["gone"]),
+		Change::Removed("log.file".to_owned(), &old["log"]["file"]),
+		Change::Modified("name".to_owned(), &old["name"], &new["name"]),
+		Change::Modified("ports.1".to_owned(), &old["ports"][1], &new["ports"][1]),
+		Change::Removed("ports.2".to_owned(), &old["ports"][2]),
+	]);
+	
+	assert!(old.diff(&old).is_empty());
+	assert_eq!(old.diff(&new["name"]), [Change::Modified(String::new(), &old, &new["name"])]);
+}
+
+
+#[test]
+fn hash() {
+	use std::{collections::HashSet, hash::{BuildHasher, RandomState}};
+	
+	let keys: Vec<_> = (0..32).map(|i| i.to_string()).collect();
+	let forward = JsefValue::Dict(JsefValue::dict_from_iter(keys.iter().map(|k| (k.clone(), k.as_str()))));
+	let backward = JsefValue::Dict(JsefValue::dict_from_iter(keys.iter().rev().map(|k| (k.clone(), k.as_str()))));
+	
+	let state = RandomState::new();
+	assert_eq!(forward, backward);
+	assert_eq!(state.hash_one(&forward), state.hash_one(&backward));
+	
+	let set: HashSet<_> = [forward, backward, JsefValue::new_dict(), JsefValue::new_list()].into_iter().collect();
+	assert_eq!(set.len(), 3);
+	assert_ne!(state.hash_one(JsefValue::new_dict()), state.hash_one(JsefValue::new_list()));
+}
+
+
+#[test]
+fn max_width() {
+	let list = parse_list("alpha beta gamma delta epsilon [x y z]").unwrap();
+	
+	let opts = ComposeOpts::COMPACT.max_width(10);
+	let composed = compose_list(&list, &opts).unwrap();
+	assert_eq!(composed, "alpha beta\ngamma delta\nepsilon [x\ny z]");
+	assert_eq!(parse_list(&composed).unwrap(), list);
+	
+	let opts = opts.list_separator(", ");
+	assert_eq!(compose_list(&list, &opts).unwrap(), "alpha, beta,\ngamma, delta,\nepsilon, [x,\ny, z]");
+	
+	let opts = ComposeOpts::PRETTY.max_width(1).inline_threshold(3);
+	assert_eq!(compose_value(&list[5], &opts), compose_value(&list[5], &opts.no_max_width()));
+}
+
+
+#[test]
+fn fold_dicts_from_depth() {
+	let dict = parse_dict("a.b.c=x").unwrap();
+	let opts = ComposeOpts::COMPACT.fold_dicts(true);
+	
+	assert_eq!(compose_dict(&dict, &opts).unwrap(), "a.b.c=x");
+	assert_eq!(compose_dict(&dict, &opts.clone().fold_dicts_from_depth(1)).unwrap(), "a={b.c=x}");
+	assert_eq!(compose_dict(&dict, &opts.clone().fold_dicts_from_depth(2)).unwrap(), "a={b={c=x}}");
+	
+	let composed = compose_dict(&dict, &ComposeOpts::PRETTY.fold_dicts_from_depth(1)).unwrap();
+	assert_eq!(composed, "a = {\n\tb.c = x\n}");
+	assert_eq!(parse_dict(&composed).unwrap(), dict);
+	
+	// The same as {a={b={c=x}}}, which is nested too deeply to parse with the test depth limit
+	let value = JsefValue::Dict(dict);
+	assert_eq!(compose_value(&value, &opts.clone().fold_dicts_from_depth(1)).unwrap(), "{a={b.c=x}}");
+	assert_eq!(compose_value(&value, &opts).unwrap(), "{a.b.c=x}");
+}
+
+
+#[test]
+fn try_from() {
+	fn convert<T: TryFrom<JsefValue, Error = JsefValue>>(value: JsefValue) -> Result<T, JsefValue> {
+		T::try_from(value)
+	}
+	
+	let dict = parse_value("{a=[x y]}").unwrap();
+	assert_eq!(convert::<JsefList>(dict.clone()), Err(dict.clone()));
+	
+	let mut dict = JsefDict::try_from(dict).unwrap();
+	let list = JsefList::try_from(dict.remove("a").unwrap()).unwrap();
+	assert_eq!(convert::<String>(list[0].clone()).unwrap(), "x");
+	assert_eq!(String::try_from(JsefValue::List(list.clone())), Err(JsefValue::List(list)));
+}
+
+
+#[test]
+fn from_scalars() {
+	assert_eq!(JsefValue::from(true), "true");
+	assert_eq!(JsefValue::from(false).as_bool(), Some(false));
+	assert_eq!(JsefValue::from(-12i64), "-12");
+	assert_eq!(JsefValue::from(u64::MAX).as_u64(), Some(u64::MAX));
+	assert_eq!(JsefValue::from(1.0), "1");
+	assert_eq!(JsefValue::from(0.1 + 0.2).as_f64(), Some(0.1 + 0.2));
+	assert_eq!(JsefValue::from(f64::NEG_INFINITY), "-inf");
+	
+	let count = 3u64;
+	assert_eq!(jsef!({count: count, neg: (-1i64)}), parse_value("{count=3 neg=-1}").unwrap());
+}
+
+
+#[test]
+fn skip_lines() {
+	let source = "%YAML-ish header\n!binary stuff\na=1\nb=[x\n";
+	assert!(parse_dict(source).is_err());
+	
+	let opts = ParseOpts::DEFAULT.skip_lines(2);
+	let err = JsefErr::new(JsefErrType::Mismatch(']', None), 5, 1, 40).in_key("b");
+	assert_eq!(parse_dict_with(source, &opts), Err(err));
+	assert_eq!(parse_dict_with("x}\na=1", &ParseOpts::DEFAULT.skip_lines(1)).unwrap(), parse_dict("a=1").unwrap());
+	assert_eq!(parse_value_with("only\nheader", &ParseOpts::DEFAULT.skip_lines(5)), Err(JsefErr::new(JsefErrType::Unexpected(None), 2, 7, 11)));
+}
+
+
+#[test]
+fn parse_events() {
+	use std::io::BufReader;
+	use Event::*;
+	
+	let source = "name = \"my app\"\nports = [80 443]\nlog.level = debug\nempty = {}";
+	let expected = [
+		DictStart,
+		Key("name".into()), StringValue("my app".into()),
+		Key("ports".into()), ListStart, StringValue("80".into()), StringValue("443".into()), ListEnd,
+		Key("log".into()), DictStart, Key("level".into()), StringValue("debug".into()), DictEnd,
+		Key("empty".into()), DictStart, DictEnd,
+		DictEnd,
+	];
+	
+	for capacity in [1, 4, 64] {
+		let mut events = Vec::new();
+		let reader = BufReader::with_capacity(capacity, source.as_bytes());
+		super::parse_events(reader, |event| events.push(event.into_owned())).unwrap();
+		assert_eq!(events, expected);
+	}
+	
+	// Events of a pair are passed on before the rest of the input is read
+	let mut events = Vec::new();
+	let reader = "a=x\nb=[y".as_bytes();
+	let result = super::parse_events(reader, |event| events.push(event.into_owned()));
+	assert_eq!(result, Err(JsefErr::new(JsefErrType::Mismatch(']', None), 2, 5, 8).in_key("b")));
+	assert_eq!(events, [DictStart, Key("a".into()), StringValue("x".into()), Key("b".into()), ListStart, StringValue("y".into())]);
+	
+	for source in ["a=[x y", "a={b=[[c]]}", "a=b}", "a.=b", "x\ny=z"] {
+		let result = super::parse_events(source.as_bytes(), |_| {});
+		assert_eq!(result, parse_dict_from(source.as_bytes()).map(drop));
+		assert!(result.is_err());
+	}
+}
+
+
+#[test]
+fn word_scanning() {
+	assert_eq!(parse_list("héllo wörld_✓ ä").unwrap(), parse_list("\"héllo\" \"wörld_✓\" \"ä\"").unwrap());
+	assert_eq!(parse_value("ab\u{85}"), Err(JsefErr::new(JsefErrType::NotEof('\u{85}'), 1, 3, 2)));
+	
+	let opts = ParseOpts::DEFAULT.comment_lead("//");
+	assert_eq!(parse_list_with("a#b c//d", &opts).unwrap(), parse_list("\"a#b\" c").unwrap());
 }
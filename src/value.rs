@@ -1,7 +1,21 @@
-use crate::{JsefList, JsefDict};
+use std::{
+	iter, fmt, mem, vec,
+	collections::HashMap,
+	hash::{Hash, Hasher, DefaultHasher},
+	ops::{Index, IndexMut},
+	str::FromStr,
+};
 
+use crate::{
+	JsefList, JsefDict,
+	JsefErr, JsefErrType, JsefResult,
+	DEPTH_LIMIT,
+	join_path, parse_value, parse_dict,
+	diff::{self, Change},
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum JsefValue {
 	String(String),
 	List(JsefList),
@@ -26,6 +40,25 @@ macro_rules! take {
 	};
 }
 
+/// The kind of a [`JsefValue`], without its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JsefKind {
+	String,
+	List,
+	Dict,
+}
+
+impl fmt::Display for JsefKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::String => write!(f, "string"),
+			Self::List   => write!(f, "list"),
+			Self::Dict   => write!(f, "dict"),
+		}
+	}
+}
+
+
 impl JsefValue {
 	pub fn new_string() -> Self {
 		Self::String(String::new())
@@ -39,6 +72,63 @@ impl JsefValue {
 		Self::Dict(JsefDict::default())
 	}
 	
+	pub fn list_with_capacity(capacity: usize) -> Self {
+		Self::List(JsefList::with_capacity(capacity))
+	}
+	
+	pub fn dict_with_capacity(capacity: usize) -> Self {
+		let mut dict = JsefDict::default();
+		dict.reserve(capacity);
+		Self::Dict(dict)
+	}
+	
+	/// Creates a list from `values`, reserving space for all of them up front.
+	pub fn from_values<I, T>(values: I) -> Self
+	where
+		I: IntoIterator<Item = T>,
+		T: Into<JsefValue>,
+	{
+		Self::List(Self::list_from_iter(values))
+	}
+	
+	/// Creates a dict from `pairs`, reserving space for all of them up front.
+	pub fn from_pairs<I, K, V>(pairs: I) -> Self
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: Into<String>,
+		V: Into<JsefValue>,
+	{
+		Self::Dict(Self::dict_from_iter(pairs))
+	}
+	
+	/// Like [`from_values`](Self::from_values), but returns the bare [`JsefList`].
+	pub fn list_from_iter<I, T>(values: I) -> JsefList
+	where
+		I: IntoIterator<Item = T>,
+		T: Into<JsefValue>,
+	{
+		let values = values.into_iter();
+		let mut list = JsefList::with_capacity(values.size_hint().0);
+		
+		list.extend(values.map(Into::into));
+		list
+	}
+	
+	/// Like [`from_pairs`](Self::from_pairs), but returns the bare [`JsefDict`].
+	pub fn dict_from_iter<I, K, V>(pairs: I) -> JsefDict
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: Into<String>,
+		V: Into<JsefValue>,
+	{
+		let pairs = pairs.into_iter();
+		let mut dict = JsefDict::default();
+		
+		dict.reserve(pairs.size_hint().0);
+		dict.extend(pairs.map(|(key, val)| (key.into(), val.into())));
+		dict
+	}
+	
 	
 	pub fn string_from<T>(value: T) -> Self
 	where T: Into<String> {
@@ -56,6 +146,15 @@ impl JsefValue {
 	}
 	
 	
+	pub fn kind(&self) -> JsefKind {
+		match self {
+			Self::String(_) => JsefKind::String,
+			Self::List(_) => JsefKind::List,
+			Self::Dict(_) => JsefKind::Dict,
+		}
+	}
+	
+	
 	pub fn is_string(&self) -> bool {
 		matches!(self, Self::String(_))
 	}
@@ -64,14 +163,70 @@ impl JsefValue {
 		get!(self, Self::String(s) => s)
 	}
 	
+	pub fn as_str(&self) -> Option<&str> {
+		get!(self, Self::String(s) => s.as_str())
+	}
+	
 	pub fn as_string_mut(&mut self) -> Option<&mut String> {
 		get!(self, Self::String(s) => s)
 	}
 	
+	/// Parses the string as an `i64`, returning `None` for non-strings and unparseable strings.
+	/// 
+	/// Follows [`str::parse`], so a leading `+` is accepted but surrounding whitespace isn't.
+	pub fn as_i64(&self) -> Option<i64> {
+		self.as_str()?.parse().ok()
+	}
+	
+	/// Parses the string as a `u64`, see [`as_i64`](Self::as_i64).
+	pub fn as_u64(&self) -> Option<u64> {
+		self.as_str()?.parse().ok()
+	}
+	
+	/// Parses the string as an `f64`, see [`as_i64`](Self::as_i64).
+	/// Also accepts spellings like `inf` and `NaN`.
+	pub fn as_f64(&self) -> Option<f64> {
+		self.as_str()?.parse().ok()
+	}
+	
+	/// Returns `Some` if the string is exactly `true` or `false`, and `None` otherwise.
+	pub fn as_bool(&self) -> Option<bool> {
+		match self.as_str()? {
+			"true" => Some(true),
+			"false" => Some(false),
+			_ => None,
+		}
+	}
+	
 	pub fn take_string(self) -> Result<String, Self> {
 		take!(self, Self::String(s) => s)
 	}
 	
+	pub fn take_string_or(self, default: String) -> String {
+		self.take_string().unwrap_or(default)
+	}
+	
+	/// Returns the inner string.
+	/// 
+	/// # Panics
+	/// If the value isn't a string.
+	#[track_caller]
+	pub fn unwrap_string(self) -> String {
+		match self {
+			Self::String(s) => s,
+			other => panic!("called unwrap_string on a {}", other.kind()),
+		}
+	}
+	
+	/// Applies `func` to the inner string, leaving other values as they are.
+	pub fn map_string<F>(self, func: F) -> Self
+	where F: FnOnce(String) -> String {
+		match self {
+			Self::String(s) => Self::String(func(s)),
+			other => other,
+		}
+	}
+	
 	
 	pub fn is_list(&self) -> bool {
 		matches!(self, Self::List(_))
@@ -89,6 +244,22 @@ impl JsefValue {
 		take!(self, Self::List(l) => l)
 	}
 	
+	pub fn take_list_or(self, default: JsefList) -> JsefList {
+		self.take_list().unwrap_or(default)
+	}
+	
+	/// Returns the inner list.
+	/// 
+	/// # Panics
+	/// If the value isn't a list.
+	#[track_caller]
+	pub fn unwrap_list(self) -> JsefList {
+		match self {
+			Self::List(l) => l,
+			other => panic!("called unwrap_list on a {}", other.kind()),
+		}
+	}
+	
 	
 	pub fn is_dict(&self) -> bool {
 		matches!(self, Self::Dict(_))
@@ -105,6 +276,533 @@ impl JsefValue {
 	pub fn take_dict(self) -> Result<JsefDict, Self> {
 		take!(self, Self::Dict(d) => d)
 	}
+	
+	pub fn take_dict_or(self, default: JsefDict) -> JsefDict {
+		self.take_dict().unwrap_or(default)
+	}
+	
+	/// Returns the inner dict.
+	/// 
+	/// # Panics
+	/// If the value isn't a dict.
+	#[track_caller]
+	pub fn unwrap_dict(self) -> JsefDict {
+		match self {
+			Self::Dict(d) => d,
+			other => panic!("called unwrap_dict on a {}", other.kind()),
+		}
+	}
+	
+	/// Removes `key` from this dict and returns its value,
+	/// or `None` if the key is absent or this value isn't a dict.
+	pub fn remove(&mut self, key: &str) -> Option<JsefValue> {
+		self.as_dict_mut()?.remove(key)
+	}
+	
+	/// Consumes a dict value and yields its owned entries, or returns `None` for other variants.
+	pub fn into_dict_entries(self) -> Option<impl Iterator<Item = (String, JsefValue)>> {
+		self.take_dict().ok().map(IntoIterator::into_iter)
+	}
+	
+	
+	/// Returns the value at the dotted `path`, creating it and any missing dicts along the way.
+	/// 
+	/// Missing values are created as empty dicts, so the result is usually meant to be overwritten.
+	/// Like with the parser's path notation, non-dict values along the way are replaced with dicts.
+	pub fn path_entry(&mut self, path: &str) -> &mut JsefValue {
+		self.path_entry_sep(path, ".")
+	}
+	
+	/// Like [`path_entry`](Self::path_entry), but with path segments separated by `sep`.
+	pub fn path_entry_sep(&mut self, path: &str, sep: &str) -> &mut JsefValue {
+		let mut value = self;
+		
+		for key in path.split(sep) {
+			if !value.is_dict() {
+				*value = Self::new_dict();
+			}
+			
+			// unwrap should be safe, value is always a JsefValue::Dict here
+			value = value
+				.as_dict_mut()
+				.unwrap()
+				.entry(key.to_owned())
+				.or_insert_with(Self::new_dict);
+		}
+		
+		value
+	}
+	
+	/// Sets the value at the dotted `path`, returning the value that was there before.
+	/// 
+	/// Missing dicts along the way are created and, like with the parser's path notation,
+	/// non-dict values along the way are replaced with dicts.
+	pub fn set_path(&mut self, path: &str, value: JsefValue) -> Option<JsefValue> {
+		self.set_path_sep(path, ".", value)
+	}
+	
+	/// Like [`set_path`](Self::set_path), but with path segments separated by `sep`.
+	pub fn set_path_sep(&mut self, path: &str, sep: &str, value: JsefValue) -> Option<JsefValue> {
+		let (parent, key) = match path.rsplit_once(sep) {
+			Some((parent, key)) => (self.path_entry_sep(parent, sep), key),
+			None => (self, path),
+		};
+		
+		if !parent.is_dict() {
+			*parent = Self::new_dict();
+		}
+		
+		// unwrap should be safe, parent is always a JsefValue::Dict here
+		parent.as_dict_mut().unwrap().insert(key.to_owned(), value)
+	}
+	
+	/// Like [`path_entry`](Self::path_entry), but fails with [`MaxDepth`](JsefErrType::MaxDepth)
+	/// if `path` has more segments than [`DEPTH_LIMIT`].
+	/// 
+	/// The line and column of the error are always 0, since there's no source to point into.
+	pub fn try_path_entry(&mut self, path: &str) -> JsefResult<&mut JsefValue> {
+		self.try_path_entry_sep(path, ".")
+	}
+	
+	/// Like [`try_path_entry`](Self::try_path_entry), but with path segments separated by `sep`.
+	pub fn try_path_entry_sep(&mut self, path: &str, sep: &str) -> JsefResult<&mut JsefValue> {
+		if path.split(sep).count() > DEPTH_LIMIT {
+			return Err(JsefErr::new(JsefErrType::MaxDepth, 0, 0, 0));
+		}
+		
+		Ok(self.path_entry_sep(path, sep))
+	}
+	
+	
+	/// Returns the value at the dotted `path`, or `None` if a segment is missing or not a dict.
+	/// 
+	/// Like in the path notation of the parser, `a.b` is the key `b` within the dict at `a`.
+	/// Keys that contain dots themselves can only be reached with [`get_path_sep`](Self::get_path_sep).
+	pub fn get_path(&self, path: &str) -> Option<&JsefValue> {
+		self.get_path_sep(path, ".")
+	}
+	
+	/// Like [`get_path`](Self::get_path), but with path segments separated by `sep`.
+	pub fn get_path_sep(&self, path: &str, sep: &str) -> Option<&JsefValue> {
+		let mut value = self;
+		
+		for key in path.split(sep) {
+			value = value.as_dict()?.get(key)?;
+		}
+		
+		Some(value)
+	}
+	
+	/// Like [`Self::get_path`], but returns a mutable reference.
+	pub fn get_path_mut(&mut self, path: &str) -> Option<&mut JsefValue> {
+		self.get_path_mut_sep(path, ".")
+	}
+	
+	/// Like [`get_path_mut`](Self::get_path_mut), but with path segments separated by `sep`.
+	pub fn get_path_mut_sep(&mut self, path: &str, sep: &str) -> Option<&mut JsefValue> {
+		let mut value = self;
+		
+		for key in path.split(sep) {
+			value = value.as_dict_mut()?.get_mut(key)?;
+		}
+		
+		Some(value)
+	}
+	
+	/// Returns a dict containing only the values at the dotted `paths`, along with their ancestors.
+	/// 
+	/// A path naming a dict keeps the whole subtree below it. Missing paths are skipped.
+	pub fn project(&self, paths: &[&str]) -> JsefValue {
+		let mut projected = Self::new_dict();
+		
+		for path in paths {
+			if let Some(value) = self.get_path(path) {
+				*projected.path_entry(path) = value.clone();
+			}
+		}
+		
+		projected
+	}
+	
+	
+	/// Consumes the value and yields all of its leaves along with their dotted paths.
+	/// 
+	/// Leaves are strings and empty lists or dicts, list items are addressed by their index.
+	/// The tree is walked without recursion, so values of any depth are fine.
+	pub fn into_flat(self) -> impl Iterator<Item = (String, JsefValue)> {
+		let mut stack = vec![(String::new(), self)];
+		
+		iter::from_fn(move || {
+			while let Some((path, value)) = stack.pop() {
+				match value {
+					Self::List(list) if !list.is_empty() => stack.extend(list
+						.into_iter()
+						.enumerate()
+						.rev()
+						.map(|(i, val)| (join_path(&path, &i.to_string()), val))
+					),
+					
+					Self::Dict(dict) if !dict.is_empty() => stack.extend(dict
+						.into_iter()
+						.map(|(key, val)| (join_path(&path, &key), val))
+					),
+					
+					leaf => return Some((path, leaf)),
+				}
+			}
+			
+			None
+		})
+	}
+	
+	/// Yields this value and everything nested within it depth-first, along with their dotted paths.
+	/// 
+	/// Parents come before their children and the value itself has an empty path,
+	/// list items are addressed by their index and in order. Like [`into_flat`](Self::into_flat),
+	/// this doesn't recurse.
+	pub fn iter_tree(&self) -> impl Iterator<Item = (String, &JsefValue)> {
+		let mut stack = vec![(String::new(), self)];
+		
+		iter::from_fn(move || {
+			let (path, value) = stack.pop()?;
+			
+			match value {
+				Self::List(list) => stack.extend(list
+					.iter()
+					.enumerate()
+					.rev()
+					.map(|(i, val)| (join_path(&path, &i.to_string()), val))
+				),
+				
+				Self::Dict(dict) => stack.extend(dict
+					.iter()
+					.map(|(key, val)| (join_path(&path, key), val))
+				),
+				
+				Self::String(_) => {},
+			}
+			
+			Some((path, value))
+		})
+	}
+	
+	/// Returns the changes that turn this value into `other`, in no particular order.
+	/// 
+	/// Dict keys are matched by name and list items by index, so inserting into a list
+	/// shows up as modifying every item after it. Values of different kinds are modified as a whole.
+	pub fn diff<'a>(&'a self, other: &'a JsefValue) -> Vec<Change<'a>> {
+		diff::diff(self, other)
+	}
+	
+	/// Deep-merges `other` into this value, such as user settings into defaults.
+	/// 
+	/// Dicts on both sides are merged key by key, recursively.
+	/// Anything else is replaced by `other` wholesale: lists aren't concatenated,
+	/// and a dict and a non-dict at the same place always end up holding the value from `other`.
+	pub fn merge(&mut self, other: JsefValue) {
+		match (self, other) {
+			(Self::Dict(dict), Self::Dict(other)) => merge_dicts(dict, other),
+			(this, other) => *this = other,
+		}
+	}
+	
+	/// Parses `input` with [`parse_dict`](crate::parse_dict) and deep-merges it into this dict.
+	/// 
+	/// Dicts present on both sides are merged recursively,
+	/// any other value from `input` replaces the one in this dict, lists included.
+	/// Fails with [`TypeConflict`](JsefErrType::TypeConflict) at line and col 0 if this value isn't a dict,
+	/// in which case `input` isn't parsed.
+	pub fn merge_str(&mut self, input: &str) -> JsefResult {
+		let Self::Dict(dict) = self else {
+			let err = JsefErrType::TypeConflict {
+				path: String::new(),
+				expected: JsefKind::Dict,
+				found: self.kind(),
+			};
+			
+			return Err(JsefErr::new(err, 0, 0, 0));
+		};
+		
+		merge_dicts(dict, parse_dict(input)?);
+		Ok(())
+	}
+	
+	/// Returns all string leaves along with their dotted paths, like [`into_flat`](Self::into_flat).
+	/// Empty lists and dicts don't show up in the map.
+	pub fn to_flat_map(&self) -> HashMap<String, String> {
+		self.iter_tree()
+			.filter_map(|(path, val)| Some((path, val.as_str()?.to_owned())))
+			.collect()
+	}
+	
+	/// Builds nested dicts from dotted paths, the reverse of [`to_flat_map`](Self::to_flat_map).
+	/// 
+	/// Index segments of flattened lists turn into dict keys, so lists don't round-trip.
+	/// Paths are applied in sorted order, so `a.b` replaces a string at `a` with a dict.
+	pub fn from_flat_map(map: HashMap<String, String>) -> Self {
+		let mut pairs: Vec<_> = map.into_iter().collect();
+		pairs.sort_unstable();
+		
+		let mut value = Self::new_dict();
+		for (path, val) in pairs {
+			*value.path_entry(&path) = Self::String(val);
+		}
+		
+		value
+	}
+	
+	/// Overwrites `dst` with a copy of this value, reusing its allocations wherever the shapes match.
+	/// 
+	/// Same as `dst.clone_from(self)`.
+	pub fn deep_clone_into(&self, dst: &mut JsefValue) {
+		dst.clone_from(self);
+	}
+	
+	/// Returns whether the value is an empty list or dict.
+	pub fn is_empty_container(&self) -> bool {
+		match self {
+			Self::String(_) => false,
+			Self::List(list) => list.is_empty(),
+			Self::Dict(dict) => dict.is_empty(),
+		}
+	}
+	
+	/// Removes all empty lists and dicts below this value,
+	/// including ones that only become empty once their own children are removed.
+	/// 
+	/// The value itself is never removed, even if it ends up empty.
+	/// The tree is walked without recursion, so values of any depth are fine.
+	pub fn prune_empty(&mut self) {
+		enum Frame {
+			List(JsefList, vec::IntoIter<JsefValue>),
+			Dict(JsefDict, <JsefDict as IntoIterator>::IntoIter),
+		}
+		
+		impl Frame {
+			fn new(value: JsefValue) -> Result<Self, JsefValue> {
+				match value {
+					JsefValue::List(list) => Ok(Self::List(JsefList::new(), list.into_iter())),
+					JsefValue::Dict(dict) => Ok(Self::Dict(JsefDict::default(), dict.into_iter())),
+					other => Err(other),
+				}
+			}
+		}
+		
+		let root = mem::replace(self, Self::new_dict());
+		let mut stack = match Frame::new(root) {
+			Ok(frame) => vec![(frame, String::new())],
+			
+			Err(root) => {
+				*self = root;
+				return;
+			},
+		};
+		
+		// Each frame is stored along with the key it came from in its parent dict
+		while let Some((frame, _)) = stack.last_mut() {
+			let next = match frame {
+				Frame::List(_, iter) => iter.next().map(|val| (String::new(), val)),
+				Frame::Dict(_, iter) => iter.next(),
+			};
+			
+			let (key, value) = match next {
+				Some((key, value)) => match Frame::new(value) {
+					Ok(child) => {
+						stack.push((child, key));
+						continue;
+					},
+					
+					Err(value) => (key, value),
+				},
+				
+				None => {
+					// unwrap should be safe, the loop condition just checked the stack isn't empty
+					let (frame, key) = stack.pop().unwrap();
+					let value = match frame {
+						Frame::List(list, _) => Self::List(list),
+						Frame::Dict(dict, _) => Self::Dict(dict),
+					};
+					
+					if stack.is_empty() {
+						*self = value;
+						return;
+					}
+					
+					(key, value)
+				},
+			};
+			
+			if value.is_empty_container() {
+				continue;
+			}
+			
+			// unwrap should be safe, the root frame is only popped above
+			match &mut stack.last_mut().unwrap().0 {
+				Frame::List(list, _) => list.push(value),
+				Frame::Dict(dict, _) => {dict.insert(key, value);},
+			}
+		}
+	}
+}
+
+/// Deep-merges `other` into `dict`, see [`JsefValue::merge`].
+pub(crate) fn merge_dicts(dict: &mut JsefDict, other: JsefDict) {
+	for (key, val) in other {
+		match (dict.get_mut(&key), val) {
+			(Some(JsefValue::Dict(d)), JsefValue::Dict(o)) => merge_dicts(d, o),
+			(_, val) => {dict.insert(key, val);},
+		}
+	}
+}
+
+
+impl Clone for JsefValue {
+	fn clone(&self) -> Self {
+		match self {
+			Self::String(s) => Self::String(s.clone()),
+			Self::List(l) => Self::List(l.clone()),
+			Self::Dict(d) => Self::Dict(d.clone()),
+		}
+	}
+	
+	/// Reuses the strings, lists and dicts of `self` that are of the same kind as in `source`,
+	/// including the values of dict keys present on both sides.
+	fn clone_from(&mut self, source: &Self) {
+		match (self, source) {
+			(Self::String(dst), Self::String(src)) => dst.clone_from(src),
+			// Vec::clone_from calls clone_from on the items it keeps
+			(Self::List(dst), Self::List(src)) => dst.clone_from(src),
+			
+			(Self::Dict(dst), Self::Dict(src)) => {
+				dst.retain(|key, _| src.contains_key(key));
+				
+				for (key, val) in src {
+					match dst.get_mut(key) {
+						Some(dst) => dst.clone_from(val),
+						None => {dst.insert(key.clone(), val.clone());},
+					}
+				}
+			},
+			
+			(dst, src) => *dst = src.clone(),
+		}
+	}
+}
+
+/// Dicts are unordered, so their entries are hashed on their own and combined
+/// in a way that doesn't depend on the order they're iterated in.
+impl Hash for JsefValue {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		mem::discriminant(self).hash(state);
+		
+		match self {
+			Self::String(s) => s.hash(state),
+			Self::List(l) => l.hash(state),
+			
+			Self::Dict(d) => {
+				let combined = d.iter().fold(0u64, |acc, entry| {
+					let mut hasher = DefaultHasher::new();
+					entry.hash(&mut hasher);
+					acc.wrapping_add(hasher.finish())
+				});
+				
+				d.len().hash(state);
+				combined.hash(state);
+			},
+		}
+	}
+}
+
+
+/// Parses the value with [`parse_value`](crate::parse_value),
+/// so root lists and dicts need their brackets.
+impl FromStr for JsefValue {
+	type Err = JsefErr;
+	
+	fn from_str(source: &str) -> JsefResult<Self> {
+		parse_value(source)
+	}
+}
+
+macro_rules! from_display {
+	( $($ty:ty),* ) => {$(
+		impl From<$ty> for JsefValue {
+			fn from(value: $ty) -> Self {
+				Self::String(value.to_string())
+			}
+		}
+	)*};
+}
+
+// Scalars are strings, so numbers and bools are stored as they're displayed.
+// Floats use the shortest form that parses back to the same value, without an exponent:
+// `1.0` becomes `1`, `1e21` becomes `1000000000000000000000`
+// and the special values become `NaN`, `inf` and `-inf`, all of which `as_f64` accepts.
+from_display!(i64, u64, f64, bool);
+
+impl From<String> for JsefValue {
+	fn from(string: String) -> Self {
+		Self::String(string)
+	}
+}
+
+impl From<&str> for JsefValue {
+	fn from(string: &str) -> Self {
+		Self::String(string.to_owned())
+	}
+}
+
+impl From<JsefList> for JsefValue {
+	fn from(list: JsefList) -> Self {
+		Self::List(list)
+	}
+}
+
+impl From<JsefDict> for JsefValue {
+	fn from(dict: JsefDict) -> Self {
+		Self::Dict(dict)
+	}
+}
+
+/// Fails with the original value if it isn't a string, like [`JsefValue::take_string`].
+/// 
+/// # Examples
+/// 
+/// ```
+/// use jsef::JsefValue;
+/// 
+/// fn name(value: JsefValue) -> Result<String, JsefValue> {
+///     let name = String::try_from(value)?;
+///     Ok(name.to_uppercase())
+/// }
+/// 
+/// assert_eq!(name(JsefValue::from("jsef")).unwrap(), "JSEF");
+/// assert_eq!(name(JsefValue::List(Vec::new())), Err(JsefValue::List(Vec::new())));
+/// ```
+impl TryFrom<JsefValue> for String {
+	type Error = JsefValue;
+	
+	fn try_from(value: JsefValue) -> Result<Self, JsefValue> {
+		value.take_string()
+	}
+}
+
+/// Fails with the original value if it isn't a list, like [`JsefValue::take_list`].
+impl TryFrom<JsefValue> for JsefList {
+	type Error = JsefValue;
+	
+	fn try_from(value: JsefValue) -> Result<Self, JsefValue> {
+		value.take_list()
+	}
+}
+
+/// Fails with the original value if it isn't a dict, like [`JsefValue::take_dict`].
+impl TryFrom<JsefValue> for JsefDict {
+	type Error = JsefValue;
+	
+	fn try_from(value: JsefValue) -> Result<Self, JsefValue> {
+		value.take_dict()
+	}
 }
 
 impl PartialEq<str> for JsefValue {
@@ -119,6 +817,18 @@ impl PartialEq<JsefValue> for str {
 	}
 }
 
+impl PartialEq<&str> for JsefValue {
+	fn eq(&self, string: &&str) -> bool {
+		self.as_str().is_some_and(|s| s == *string)
+	}
+}
+
+impl PartialEq<JsefValue> for &str {
+	fn eq(&self, value: &JsefValue) -> bool {
+		value.as_str().is_some_and(|s| *self == s)
+	}
+}
+
 impl PartialEq<String> for JsefValue {
 	fn eq(&self, string: &String) -> bool {
 		self.as_string().is_some_and(|s| s == string)
@@ -154,3 +864,58 @@ impl PartialEq<JsefValue> for JsefDict {
 		value.as_dict().is_some_and(|d| self == d)
 	}
 }
+
+
+/// Indexes into a dict, panicking if `self` isn't one or doesn't contain `key`.
+impl Index<&str> for JsefValue {
+	type Output = JsefValue;
+	
+	#[track_caller]
+	fn index(&self, key: &str) -> &JsefValue {
+		match self {
+			Self::Dict(dict) => dict.get(key)
+				.unwrap_or_else(|| panic!("no key \"{key}\" in dict")),
+			
+			other => panic!("cannot index a {} with a key", other.kind()),
+		}
+	}
+}
+
+/// Indexes into a dict, inserting an empty string at `key` if it's missing
+/// so that assignments like `value["key"] = x` work. Panics if `self` isn't a dict.
+impl IndexMut<&str> for JsefValue {
+	#[track_caller]
+	fn index_mut(&mut self, key: &str) -> &mut JsefValue {
+		match self {
+			Self::Dict(dict) => dict
+				.entry(key.to_owned())
+				.or_insert_with(Self::new_string),
+			
+			other => panic!("cannot index a {} with a key", other.kind()),
+		}
+	}
+}
+
+/// Indexes into a list, panicking if `self` isn't one or `idx` is out of bounds.
+impl Index<usize> for JsefValue {
+	type Output = JsefValue;
+	
+	#[track_caller]
+	fn index(&self, idx: usize) -> &JsefValue {
+		match self {
+			Self::List(list) => &list[idx],
+			other => panic!("cannot index a {} with an index", other.kind()),
+		}
+	}
+}
+
+/// Indexes into a list, panicking if `self` isn't one or `idx` is out of bounds.
+impl IndexMut<usize> for JsefValue {
+	#[track_caller]
+	fn index_mut(&mut self, idx: usize) -> &mut JsefValue {
+		match self {
+			Self::List(list) => &mut list[idx],
+			other => panic!("cannot index a {} with an index", other.kind()),
+		}
+	}
+}